@@ -0,0 +1,14 @@
+-- One row per link or property of a finger: links set rel and href,
+-- properties set property_key and property_value. A subject of
+-- acct:*@<domain> answers for any user of that domain
+CREATE TABLE fingers (
+    id INTEGER PRIMARY KEY,
+    domain TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    rel TEXT,
+    href TEXT,
+    property_key TEXT,
+    property_value TEXT
+);
+
+CREATE INDEX fingers_lookup ON fingers (domain, subject);
@@ -0,0 +1,143 @@
+use axum::{
+    body::{Body, HttpBody},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, Method, StatusCode, Uri, Version},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    io::Write,
+    net::{IpAddr, SocketAddr},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::rate_limit::client_ip;
+use crate::AppState;
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    Clf,
+    // CLF plus the referer and user agent
+    Combined,
+}
+
+// One request, as written to the access log
+pub struct Entry<'a> {
+    pub ip: Option<IpAddr>,
+    pub method: &'a Method,
+    pub uri: &'a Uri,
+    pub version: Version,
+    pub status: StatusCode,
+    // Body size before compression, unknown (logged as "-") for streamed bodies
+    pub bytes: Option<u64>,
+    pub referer: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub time: SystemTime,
+}
+
+impl Entry<'_> {
+    pub fn line(&self, format: AccessLogFormat, redact: bool) -> String {
+        let ip = self.ip.map_or_else(|| "-".to_string(), |ip| ip.to_string());
+        let target = match self.uri.query() {
+            Some(query) if redact => format!("{}?{}", self.uri.path(), redact_resources(query)),
+            Some(query) => format!("{}?{}", self.uri.path(), query),
+            None => self.uri.path().to_string(),
+        };
+        let bytes = self.bytes.map_or_else(|| "-".to_string(), |bytes| bytes.to_string());
+        let mut line = format!(
+            "{} - - [{}] \"{} {} {:?}\" {} {}",
+            ip,
+            clf_time(self.time),
+            self.method,
+            escape(&target),
+            self.version,
+            self.status.as_u16(),
+            bytes
+        );
+        if format == AccessLogFormat::Combined {
+            let quoted = |value: Option<&str>| value.map_or_else(|| "-".to_string(), escape);
+            line.push_str(&format!(" \"{}\" \"{}\"", quoted(self.referer), quoted(self.user_agent)));
+        }
+        line
+    }
+}
+
+// Writes one line per request to stdout, apart from the tracing logs
+pub async fn log(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(format) = state.settings.access_log else {
+        return next.run(request).await;
+    };
+    
+    let ip = client_ip(request.headers(), connect_info.map(|c| c.0), state.settings.trust_forwarded_headers);
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+    let version = request.version();
+    let referer = header_text(request.headers(), header::REFERER);
+    let user_agent = header_text(request.headers(), header::USER_AGENT);
+    let time = SystemTime::now();
+    
+    let response: Response<Body> = next.run(request).await;
+    let entry = Entry {
+        ip,
+        method: &method,
+        uri: &uri,
+        version,
+        status: response.status(),
+        bytes: response.body().size_hint().exact(),
+        referer: referer.as_deref(),
+        user_agent: user_agent.as_deref(),
+        time,
+    };
+    let _ = writeln!(std::io::stdout().lock(), "{}", entry.line(format, state.settings.access_log_redact));
+    response
+}
+
+fn header_text(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string)
+}
+
+// resource values name users, which some deployments keep out of logs
+fn redact_resources(query: &str) -> String {
+    query
+        .split('&')
+        .map(|pair| if pair.starts_with("resource=") { "resource=redacted" } else { pair })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+// Quotes and backslashes would break the quoted fields
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// 10/Oct/2000:13:55:36 +0000, always in UTC
+fn clf_time(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+    // Days since the epoch to a civil date (Howard Hinnant's days_from_civil, inverted)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
@@ -0,0 +1,106 @@
+use axum::{
+    http::{HeaderMap, Uri},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::{sync::Arc, time::Instant};
+
+use crate::config::{normalize_domain, WebFinger};
+use crate::error::{BatchError, WebFingerError};
+use crate::telemetry::{self, Outcome};
+use crate::{
+    find_finger, lookup_domain, minimal_finger, normalize_resource, redirect_to_canonical, request_domain,
+    subject_location, AppState, WebFingerQuery,
+};
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BatchEntry<'a> {
+    Found(Arc<WebFinger>),
+    // An old handle, with the single lookup its client should make instead
+    Moved { status: u16, location: String },
+    Failed(BatchError<'a>),
+}
+
+pub fn requested(batch: Option<&str>) -> bool {
+    matches!(batch, Some("1" | "true"))
+}
+
+// Rate limiter tokens a request costs: one per resource of a batch, counting
+// no further than the largest batch accepted, and one for anything else
+pub fn cost(uri: &Uri, max_resources: usize) -> f64 {
+    let pairs = || url::form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes());
+    if uri.path() != "/.well-known/webfinger" || !pairs().any(|(key, value)| key == "batch" && requested(Some(&value))) {
+        return 1.0;
+    }
+    pairs().filter(|(key, _)| key == "resource").count().clamp(1, max_resources.max(1)) as f64
+}
+
+// ?batch=1: every resource is resolved on its own and answered in one JSON
+// array, in request order, with failures as error entries instead of statuses.
+// Like a single lookup it honors minimal=1 and redirects a secondary domain to
+// the canonical one, but it always answers JSON and is never signed or cached
+pub async fn batch_response(
+    headers: &HeaderMap,
+    uri: &Uri,
+    params: &WebFingerQuery,
+    state: &AppState,
+    started: Instant,
+) -> Result<Response, WebFingerError> {
+    if params.resource.is_empty() {
+        telemetry::record_webfinger(telemetry::UNKNOWN_TENANT, Outcome::BadRequest, started);
+        return Err(WebFingerError::MissingResource);
+    }
+    if params.resource.len() > state.settings.batch_max_resources {
+        telemetry::record_webfinger(telemetry::UNKNOWN_TENANT, Outcome::BadRequest, started);
+        return Err(WebFingerError::TooManyResources);
+    }
+    
+    let host = match request_domain(headers, uri, &state.settings) {
+        Ok(host) => normalize_domain(host),
+        Err(e) => {
+            telemetry::record_webfinger(telemetry::UNKNOWN_TENANT, Outcome::BadRequest, started);
+            return Err(e);
+        }
+    };
+    // Off an aggregator host every resource is looked up on the request host,
+    // so a secondary domain redirects the whole batch
+    if !state.settings.aggregator_hosts.contains(&host) {
+        let config = state.config.load();
+        if let Some(redirect) = redirect_to_canonical(config.tenant_for_domain(&host), &host, &host, uri, started) {
+            return Ok(redirect);
+        }
+    }
+    
+    let minimal = matches!(params.minimal.as_deref(), Some("1" | "true"));
+    let mut results: Vec<Result<Arc<WebFinger>, WebFingerError>> = Vec::with_capacity(params.resource.len());
+    for resource in &params.resource {
+        let resource = normalize_resource(resource);
+        let domain = lookup_domain(&host, &resource, &state.settings);
+        results.push(match find_finger(state, &domain, resource, &params.rel).await {
+            Ok(resolved) => {
+                let outcome = if resolved.finger.redirect_to.is_some() { Outcome::Redirect } else { resolved.outcome };
+                telemetry::record_webfinger(&resolved.tenant, outcome, started);
+                Ok(resolved.finger)
+            }
+            Err((tenant, e)) => {
+                telemetry::record_webfinger(&tenant, e.outcome(), started);
+                Err(e)
+            }
+        });
+    }
+    
+    let entries: Vec<BatchEntry> = results
+        .iter()
+        .map(|result| match result {
+            Ok(finger) => match &finger.redirect_to {
+                Some(target) => BatchEntry::Moved { status: 307, location: subject_location(target, uri) },
+                None if minimal => BatchEntry::Found(Arc::new(minimal_finger(finger))),
+                None => BatchEntry::Found(finger.clone()),
+            },
+            Err(e) => BatchEntry::Failed(e.batch_entry()),
+        })
+        .collect();
+    Ok(Json(entries).into_response())
+}
@@ -0,0 +1,91 @@
+use axum::body::Bytes;
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use crate::telemetry::Outcome;
+use crate::xrd::Format;
+
+// Upper bound on cached responses, global tenants can produce unbounded keys
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct CacheKey {
+    domain: String,
+    resource: String,
+    rels: Vec<String>,
+    format: Format,
+    minimal: bool,
+}
+
+impl CacheKey {
+    pub fn new(domain: &str, resource: &str, rels: &[String], format: Format, minimal: bool) -> Self {
+        // Rel order and repetition don't change the response
+        let mut rels = rels.to_vec();
+        rels.sort();
+        rels.dedup();
+        CacheKey {
+            domain: domain.to_string(),
+            resource: resource.to_string(),
+            rels,
+            format,
+            minimal,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CachedResponse {
+    pub body: Bytes,
+    pub tenant: String,
+    pub outcome: Outcome,
+    expires: Instant,
+}
+
+// Serialized responses with a fixed time to live
+pub struct ResponseCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<CacheKey, CachedResponse>>,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration) -> Self {
+        ResponseCache {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+    
+    pub fn get(&self, key: &CacheKey) -> Option<CachedResponse> {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(key)
+            .filter(|entry| entry.expires > Instant::now())
+            .cloned()
+    }
+    
+    pub fn insert(&self, key: CacheKey, body: Bytes, tenant: &str, outcome: Outcome) {
+        let now = Instant::now();
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, entry| entry.expires > now);
+            if entries.len() >= MAX_ENTRIES {
+                return; // Still full of live entries, skip caching this one
+            }
+        }
+        
+        entries.insert(key, CachedResponse {
+            body,
+            tenant: tenant.to_string(),
+            outcome,
+            expires: now + self.ttl,
+        });
+    }
+    
+    pub fn clear(&self) {
+        self.entries.write().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
@@ -0,0 +1,70 @@
+use std::{env, path::PathBuf};
+
+const USAGE: &str = "\
+Usage: rustfinger [OPTIONS]
+
+Options:
+  --check-config  Validate the configuration and exit
+  --dump-config   Print every configured finger as JSON and exit
+  --import FILE   Print a tenant file for the subject,rel,href rows of a .csv
+                  or .json list, report rejected rows and exit 1 if any
+  --lint          Report unused URN aliases and keys no alias maps to, exit 1 if any
+  --print-schema  Print the JSON Schema of tenant config files and exit
+  --watch         Re-validate the config on every change and print a summary,
+                  with --dump-config also the dump of each good config
+  -h, --help      Print this help and exit";
+
+pub enum Command {
+    Serve,
+    CheckConfig,
+    DumpConfig,
+    Lint,
+    // Rows of a CSV or JSON user list, as a tenant file
+    Import(PathBuf),
+    PrintSchema,
+    // Developer mode, validating instead of serving
+    Watch { dump: bool },
+    Help,
+}
+
+impl Command {
+    pub fn from_args() -> Result<Self, String> {
+        Self::parse(env::args().skip(1))
+    }
+    
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut command = Command::Serve;
+        let mut watch = false;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            command = match arg.as_str() {
+                "--import" => match args.next() {
+                    Some(path) => Command::Import(path.into()),
+                    None => return Err(format!("--import needs a file\n\n{}", USAGE)),
+                },
+                "--watch" => {
+                    watch = true;
+                    continue;
+                }
+                "--check-config" => Command::CheckConfig,
+                "--dump-config" => Command::DumpConfig,
+                "--lint" => Command::Lint,
+                "--print-schema" => Command::PrintSchema,
+                "-h" | "--help" => return Ok(Command::Help),
+                _ => return Err(format!("Unknown argument '{}'\n\n{}", arg, USAGE)),
+            };
+        }
+        if !watch {
+            return Ok(command);
+        }
+        match command {
+            Command::Serve => Ok(Command::Watch { dump: false }),
+            Command::DumpConfig => Ok(Command::Watch { dump: true }),
+            _ => Err(format!("--watch only combines with --dump-config\n\n{}", USAGE)),
+        }
+    }
+}
+
+pub fn print_usage() {
+    println!("{}", USAGE);
+}
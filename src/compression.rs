@@ -0,0 +1,19 @@
+use axum::http::{Extensions, HeaderMap, StatusCode, Version};
+use tower_http::compression::{
+    predicate::{DefaultPredicate, Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+use crate::settings::Settings;
+use crate::signature::SIGNATURE;
+
+// Gzip or brotli, whichever the client prefers. Bodies under the threshold
+// (health checks, errors, most single-link JRDs) are sent as-is, and so are
+// signed responses, whose Content-Digest covers the uncompressed body
+pub fn layer(settings: &Settings) -> CompressionLayer<impl Predicate> {
+    let unsigned = |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| !headers.contains_key(SIGNATURE);
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .compress_when(DefaultPredicate::new().and(SizeAbove::new(settings.compression_min_size)).and(unsigned))
+}
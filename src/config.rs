@@ -1,150 +1,1100 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use ed25519_dalek::SigningKey;
+use indexmap::IndexMap;
 use regex::Regex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+};
+use tracing::{debug, info, warn};
+use unicode_normalization::UnicodeNormalization;
 use url::Url;
 
+use crate::{settings, signature};
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Link {
     pub rel: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub href: Option<String>,
+    // URI template in place of an href, as used by host-meta (RFC 6415)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub template: Option<String>,
+    #[serde(skip_serializing_if = "IndexMap::is_empty", default)]
+    pub titles: IndexMap<String, String>,
+    #[serde(skip_serializing_if = "IndexMap::is_empty", default)]
+    pub properties: IndexMap<String, Option<String>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WebFinger {
     pub subject: String,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub aliases: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub links: Vec<Link>,
-    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
-    pub properties: HashMap<String, String>,
+    #[serde(skip_serializing_if = "IndexMap::is_empty", default)]
+    pub properties: IndexMap<String, Option<String>>,
+    // Canonical subject to send clients to instead, never part of the JRD
+    #[serde(skip)]
+    pub redirect_to: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct UserConfig {
+    // Subject to answer with, when it differs from the key users look up
+    #[serde(default)]
+    pub subject: Option<String>,
+    // Alternate URIs identifying the same subject
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    // Other accounts of the same person, e.g. before or after a migration
+    #[serde(default)]
+    pub also_known_as: Vec<String>,
+    // Answer lookups with a 307 to this acct: subject, e.g. from an old handle
+    #[serde(default)]
+    pub redirect_to: Option<String>,
+    // Explicit sections, so values are never guessed from their shape
+    #[serde(default)]
+    pub links: Entries,
+    // A null value (~) is a known but empty property
+    #[serde(default)]
+    pub properties: IndexMap<String, Option<String>>,
+    // Legacy flat entries, URLs become links and anything else a property
+    #[serde(flatten)]
+    pub entries: Entries,
+}
+
+// Entries keep their order from the file, so links are served as declared
+pub type Entries = IndexMap<String, UserValue>;
+
+// A user entry is either a plain value, null, or a structured link
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum UserValue {
+    Text(String),
+    Link(LinkConfig),
+    // Only meaningful as a property, a link needs an href
+    Null,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct LinkConfig {
+    pub href: String,
+    // Relations to emit instead of the entry key
+    #[serde(default)]
+    pub rels: Vec<String>,
+    #[serde(default)]
+    pub titles: IndexMap<String, String>,
+    // Title in the tenant's language, or "und" when it has none
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub properties: IndexMap<String, Option<String>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum OpenIdConfig {
+    Issuer(String),
+    Issuers(Vec<IssuerConfig>),
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct IssuerConfig {
+    pub href: String,
+    // Lower is preferred. Issuers without one follow, in the order written
+    #[serde(default)]
+    pub priority: Option<u32>,
+}
+
+impl OpenIdConfig {
+    // Most preferred first, with the priority to state on each link, if any
+    pub fn issuers(&self) -> Vec<(&str, Option<u32>)> {
+        let mut issuers: Vec<_> = match self {
+            OpenIdConfig::Issuer(href) => vec![(href.as_str(), None)],
+            OpenIdConfig::Issuers(issuers) => {
+                issuers.iter().map(|issuer| (issuer.href.as_str(), issuer.priority)).collect()
+            }
+        };
+        issuers.sort_by_key(|(_, priority)| priority.unwrap_or(u32::MAX));
+        issuers
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct TenantConfig {
-    pub domain: String,
     #[serde(default)]
-    pub users: HashMap<String, HashMap<String, String>>,
+    pub domain: Option<String>,
+    // Additional domains serving the same users
+    #[serde(default)]
+    pub domains: Vec<String>,
+    #[serde(default)]
+    pub users: HashMap<String, UserConfig>,
     #[serde(default)]
     pub global: bool,
+    // With false, a global tenant only answers for its listed users
+    #[serde(default = "default_true")]
+    pub confirm_unknown_users: bool,
+    // One issuer URL, or several as a list of {href, priority}
+    #[serde(default)]
+    pub openid: Option<OpenIdConfig>,
+    // Answer mailto: resources like their acct: equivalent
+    #[serde(default)]
+    pub accept_mailto: bool,
+    // Match acct:bob+tag@domain to a stored acct:bob@domain
+    #[serde(default)]
+    pub strip_plus_tags: bool,
+    // Language tag sent as Content-Language and used for untagged titles
+    #[serde(default)]
+    pub language: Option<String>,
+    // Links and properties served to any user of a global tenant
+    #[serde(default)]
+    pub global_links: Entries,
+    // Like global_links, for users of subdomains matching a pattern such as
+    // "team.example.com" or "*.example.com". Hrefs may also use {subdomain}
+    #[serde(default)]
+    pub wildcard_subdomains: HashMap<String, Entries>,
+    // Links and properties given to every listed user, hrefs may use {user} and {domain}
     #[serde(default)]
-    pub openid: Option<String>,
+    pub link_templates: Entries,
+    // Serve requests for domains no tenant claims
+    #[serde(default)]
+    pub default: bool,
+    // Domain that the other domains redirect to
+    #[serde(default)]
+    pub canonical_domain: Option<String>,
+    // NodeInfo document linked from every finger of this tenant
+    #[serde(default)]
+    pub nodeinfo: Option<String>,
+    // Tenant-local URN aliases, taking precedence over urns.yml
+    #[serde(default)]
+    pub urn_aliases: URNAliases,
+    // PEM (PKCS#8) Ed25519 private key used to sign responses
+    #[serde(default)]
+    pub signing_key: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl TenantConfig {
+    fn all_domains(&self) -> Vec<String> {
+        let mut domains: Vec<String> = Vec::new();
+        for domain in self.domain.iter().chain(&self.domains) {
+            let domain = normalize_domain(domain);
+            if !domains.contains(&domain) {
+                domains.push(domain);
+            }
+        }
+        domains
+    }
+    
+    fn merged_aliases(&self, global: &URNAliases) -> URNAliases {
+        let mut aliases = global.clone();
+        aliases.extend(self.urn_aliases.iter().map(|(key, urn)| (key.clone(), urn.clone())));
+        aliases
+    }
 }
 
-type URNAliases = HashMap<String, String>;
+pub const CONFIG_DIR: &str = "config";
+// URN alias files that live next to the tenants, e.g. config/social.urns.yml
+const URN_ALIAS_FILE_SUFFIX: &str = ".urns.yml";
+
+pub const NODEINFO_REL: &str = "http://nodeinfo.diaspora.software/ns/schema/2.1";
+pub const ALSO_KNOWN_AS_REL: &str = "https://www.w3.org/ns/activitystreams#alsoKnownAs";
+pub const OPENID_ISSUER_REL: &str = "http://openid.net/specs/connect/1.0/issuer";
+// URN alias a tenant's `openid` issuer link is served under. Without that
+// alias in urns.yml the rel is OPENID_ISSUER_REL, never the bare "openid"
+const OPENID_ALIAS: &str = "openid";
+// Property on issuer links configured with a priority, lower is preferred
+pub const ISSUER_PRIORITY_PROPERTY: &str = "https://github.com/Mimmikk/RustFinger#priority";
+
+// Compiled once on first use
+static EMAIL_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    // Local parts may be Unicode (RFC 6531), punycode TLDs ("xn--p1ai") contain digits and hyphens
+    Regex::new(r"^[\p{L}\p{M}\p{N}._%+-]+@[a-zA-Z0-9.-]+\.([a-zA-Z]{2,}|xn--[a-zA-Z0-9-]+)$").expect("email regex is valid")
+});
+
+// BCP 47 shape, e.g. "en" or "en-US", without checking the registry
+static LANGUAGE_TAG_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^[A-Za-z]{1,8}(-[A-Za-z0-9]{1,8})*$").expect("language tag regex is valid")
+});
+
+// Title key when the tenant has no language (RFC 7033 section 4.4.4.3)
+const UNDETERMINED_LANGUAGE: &str = "und";
+
+pub type URNAliases = HashMap<String, String>;
 type TenantsConfig = HashMap<String, TenantConfig>;
 
+// JSON Schema of a tenants file, generated from the structs above
+pub fn schema() -> String {
+    let schema = schemars::schema_for!(TenantsConfig);
+    serde_json::to_string_pretty(&schema).expect("schema serializes")
+}
+// Tenant definitions keyed by name, along with the file each came from
+type LoadedTenants = HashMap<String, (PathBuf, TenantConfig)>;
+
 #[derive(Debug)]
 pub struct TenantData {
-    pub domain: String,
+    pub name: String,
+    pub domains: Vec<String>,
     pub global: bool,
-    pub fingers: HashMap<String, WebFinger>,
+    pub accept_mailto: bool,
+    pub strip_plus_tags: bool,
+    pub language: Option<String>,
+    pub canonical_domain: Option<String>,
+    // urns.yml merged with the tenant's own, for rel filters in queries
+    pub urn_aliases: URNAliases,
+    // Shared so lookups hand out references instead of deep copies
+    pub fingers: HashMap<String, Arc<WebFinger>>,
+    // wildcard_subdomains patterns, most specific first. Each one's finger
+    // is stored under acct:*@<pattern>
+    pub subdomain_patterns: Vec<String>,
+    pub signing_key: Option<SigningKey>,
+}
+
+// All problems found by the strict validation pass
+#[derive(Debug)]
+pub struct ValidationErrors(pub Vec<String>);
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} problem(s) found in config", self.0.len())?;
+        for error in &self.0 {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
 }
 
+impl std::error::Error for ValidationErrors {}
+
 pub struct Config {
-    pub tenants: HashMap<String, TenantData>,
+    pub tenants: HashMap<String, Arc<TenantData>>,
+    // Same tenants keyed by domain for the request hot path
+    pub domains: HashMap<String, Arc<TenantData>>,
+    // Tenant with `default: true`, for domains not in the map above
+    pub default_tenant: Option<Arc<TenantData>>,
+    // SHA-256 of the dump, so instances can be compared after a reload
+    pub hash: String,
+    // Warnings for flat entries that became properties but look like URLs
+    pub(crate) url_like_properties: Vec<String>,
 }
 
 impl Config {
     pub async fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::load_from_disk(settings::strict_config_from_env()).await
+    }
+    
+    // Like load, but as with STRICT_CONFIG a file that fails to parse fails it all.
+    // For validation and reloads, where skipping a file would hide the error
+    pub async fn load_strict() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::load_from_disk(true).await
+    }
+    
+    async fn load_from_disk(strict: bool) -> Result<Self, Box<dyn std::error::Error>> {
         // Load URN aliases
         let urn_aliases = load_urn_aliases().await?;
         
         // Load tenant configurations from config directory
-        let tenants = load_tenants().await?;
+        let tenants = load_tenants(Path::new(CONFIG_DIR), strict).await?;
+        
+        Self::build(tenants, urn_aliases)
+    }
+    
+    // Tenants of another directory, without URN aliases
+    #[cfg(test)]
+    pub async fn load_dir(dir: &Path, strict: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::build(load_tenants(dir, strict).await?, URNAliases::new())
+    }
+    
+    // Lint findings for the files on disk, see lint_tenants. Always strict
+    pub async fn lint() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let urn_aliases = load_urn_aliases().await?;
+        let tenants = load_tenants(Path::new(CONFIG_DIR), true).await?;
+        Ok(lint_tenants(&tenants, &urn_aliases))
+    }
+    
+    #[cfg(test)]
+    pub fn lint_yaml(yaml: &str, urn_aliases: &URNAliases) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let tenant_config: TenantsConfig = serde_yaml::from_str(yaml)?;
+        let tenants = tenant_config
+            .into_iter()
+            .map(|(name, tenant)| (name, (PathBuf::from("test.yml"), tenant)))
+            .collect();
+        Ok(lint_tenants(&tenants, urn_aliases))
+    }
+    
+    // Tenants from an in-memory YAML document, as if read from one file
+    pub fn from_yaml(yaml: &str, urn_aliases: URNAliases) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_yaml_at(yaml, Path::new("test.yml"), urn_aliases)
+    }
+    
+    // Like from_yaml, with errors naming `path` as the file. ${VAR} references
+    // are expanded as they would be in a file on disk
+    pub(crate) fn from_yaml_at(
+        yaml: &str,
+        path: &Path,
+        urn_aliases: URNAliases,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let tenant_config = interpret(serde_yaml::from_str(yaml)?, path)?;
+        let tenants = tenant_config
+            .into_iter()
+            .map(|(name, tenant)| (name, (path.to_path_buf(), tenant)))
+            .collect();
+        Self::build(tenants, urn_aliases)
+    }
+    
+    fn build(tenants: LoadedTenants, urn_aliases: URNAliases) -> Result<Self, Box<dyn std::error::Error>> {
+        // Report every problem at once rather than stopping at the first
+        let errors = validate_tenants(&tenants, &urn_aliases);
+        if !errors.is_empty() {
+            return Err(ValidationErrors(errors).into());
+        }
+        
+        let default_name = tenants
+            .iter()
+            .find(|(_, (_, tenant))| tenant.default)
+            .map(|(name, _)| name.clone());
+        let url_like_properties = url_like_entries(&tenants);
         
         // Process configurations into tenant data
         let tenant_data = process_tenants(tenants, urn_aliases)?;
+        let default_tenant = default_name.and_then(|name| tenant_data.get(&name).cloned());
         
-        Ok(Config { tenants: tenant_data })
+        // Index tenants by each of their domains
+        let domains = tenant_data
+            .values()
+            .flat_map(|tenant| tenant.domains.iter().map(move |domain| (domain.clone(), tenant.clone())))
+            .collect();
+        
+        let mut config = Config { tenants: tenant_data, domains, default_tenant, hash: String::new(), url_like_properties };
+        config.hash = format!("{:x}", Sha256::digest(config.dump()?));
+        Ok(config)
+    }
+    
+    // Zero tenants almost always means the config wasn't picked up
+    pub fn check_not_empty(&self, require_tenants: bool) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.tenants.is_empty() {
+            return Ok(());
+        }
+        if require_tenants {
+            return Err(format!("No tenants loaded from '{}' and REQUIRE_TENANTS is set", tenant_source()).into());
+        }
+        warn!("No tenants loaded from '{}', every WebFinger request will 404", tenant_source());
+        Ok(())
+    }
+    
+    // Exact domain matches take priority over the default tenant
+    pub fn tenant_for_domain(&self, domain: &str) -> Option<&TenantData> {
+        self.domains
+            .get(domain)
+            .or(self.default_tenant.as_ref())
+            .map(|tenant| tenant.as_ref())
+    }
+    
+    // Every processed finger as pretty JSON, ordered so dumps can be diffed
+    pub fn dump(&self) -> Result<String, serde_json::Error> {
+        let mut tenants = serde_json::Map::new();
+        for (name, tenant) in &self.tenants {
+            let mut fingers = serde_json::Map::new();
+            for (key, finger) in &tenant.fingers {
+                let mut value = serde_json::to_value(finger)?;
+                if let Some(target) = &finger.redirect_to {
+                    value["redirect_to"] = target.clone().into();
+                }
+                fingers.insert(key.clone(), value);
+            }
+            tenants.insert(name.clone(), serde_json::json!({
+                "domains": tenant.domains,
+                "global": tenant.global,
+                "fingers": fingers,
+            }));
+        }
+        serde_json::to_string_pretty(&tenants)
+    }
+    
+    // Flat entries that look like URLs but became properties are usually typos
+    // in what was meant to be a link
+    pub fn warn_url_like_properties(&self) {
+        for warning in &self.url_like_properties {
+            warn!("{}", warning);
+        }
     }
 }
 
-async fn load_urn_aliases() -> Result<URNAliases, Box<dyn std::error::Error>> {
-    let content = match tokio::fs::read_to_string("urns.yml").await {
-        Ok(content) => content,
-        Err(_) => return Ok(HashMap::new()), // Default empty if file doesn't exist
+pub(crate) async fn load_urn_aliases() -> Result<URNAliases, Box<dyn std::error::Error>> {
+    let aliases: URNAliases = match tokio::fs::read_to_string("urns.yml").await {
+        Ok(content) => serde_yaml::from_str(&content)?,
+        Err(_) => HashMap::new(), // Default empty if file doesn't exist
     };
     
-    let aliases: URNAliases = serde_yaml::from_str(&content)?;
+    // A single CONFIG_FILE means there is no config directory to scan
+    if config_file().is_some() {
+        return Ok(aliases);
+    }
+    merge_urn_alias_files(Path::new(CONFIG_DIR), aliases).await
+}
+
+// *.urns.yml files in the config directory, in name order, on top of urns.yml.
+// Two files giving the same alias different URNs is an error, tenants that
+// want their own meaning use urn_aliases instead
+pub async fn merge_urn_alias_files(dir: &Path, mut aliases: URNAliases) -> Result<URNAliases, Box<dyn std::error::Error>> {
+    let mut paths = Vec::new();
+    if let Ok(mut entries) = tokio::fs::read_dir(dir).await {
+        while let Some(entry) = entries.next_entry().await? {
+            if is_urn_alias_file(&entry.path()) {
+                paths.push(entry.path());
+            }
+        }
+    }
+    paths.sort();
+    
+    let mut sources: HashMap<String, PathBuf> = HashMap::new();
+    for path in paths {
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let file: URNAliases = serde_yaml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for (alias, urn) in file {
+            if let Some(existing) = sources.get(&alias) {
+                if aliases.get(&alias) != Some(&urn) {
+                    return Err(format!(
+                        "URN alias '{}' is defined differently in '{}' and '{}'",
+                        alias, existing.display(), path.display()
+                    ).into());
+                }
+            }
+            sources.insert(alias.clone(), path.clone());
+            aliases.insert(alias, urn);
+        }
+    }
     Ok(aliases)
 }
 
-async fn load_tenants() -> Result<TenantsConfig, Box<dyn std::error::Error>> {
-    let mut tenants = HashMap::new();
+fn is_urn_alias_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(URN_ALIAS_FILE_SUFFIX))
+}
+
+async fn load_tenants(config_dir: &Path, strict: bool) -> Result<LoadedTenants, Box<dyn std::error::Error>> {
+    let mut tenants: LoadedTenants = HashMap::new();
+    
+    // A single combined file replaces the directory scan
+    if let Some(path) = config_file() {
+        let tenant_config = read_tenant_file(&path)
+            .await?
+            .ok_or_else(|| format!("{}: expected a .yml, .yaml or .toml file", path.display()))?;
+        for (name, tenant) in tenant_config {
+            tenants.insert(name, (path.clone(), tenant));
+        }
+        return Ok(tenants);
+    }
     
     // Try to read config directory
-    let mut dir = match tokio::fs::read_dir("config").await {
+    let mut dir = match tokio::fs::read_dir(config_dir).await {
         Ok(dir) => dir,
         Err(_) => return Ok(tenants), // Return empty if no config dir
     };
     
-    // Read all .yml files in config directory
+    // Read every .yml, .yaml and .toml file. One that fails to parse only costs
+    // its own tenants, unless strict, where it fails the whole load
     while let Some(entry) = dir.next_entry().await? {
         let path = entry.path();
-        if let Some(ext) = path.extension() {
-            if ext == "yml" || ext == "yaml" {
-                let content = tokio::fs::read_to_string(&path).await?;
-                let tenant_config: TenantsConfig = serde_yaml::from_str(&content)?;
-                tenants.extend(tenant_config);
+        if is_urn_alias_file(&path) {
+            continue;
+        }
+        let tenant_config = match read_tenant_file(&path).await {
+            Ok(Some(tenant_config)) => tenant_config,
+            Ok(None) => continue,
+            Err(e) if !strict => {
+                warn!("Skipping config file that failed to parse: {}", e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        
+        // Refuse to let a later file silently override an earlier one
+        for (name, tenant) in tenant_config {
+            if let Some((existing, _)) = tenants.get(&name) {
+                return Err(format!(
+                    "Tenant '{}' is defined in both '{}' and '{}'",
+                    name, existing.display(), path.display()
+                ).into());
             }
+            tenants.insert(name, (path.clone(), tenant));
         }
     }
     
     Ok(tenants)
 }
 
+// Path from CONFIG_FILE, when tenants come from one file instead of CONFIG_DIR
+pub fn config_file() -> Option<PathBuf> {
+    std::env::var_os("CONFIG_FILE")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+// CONFIG_FILE or CONFIG_DIR, whichever tenants are read from
+pub fn tenant_source() -> String {
+    config_file().map_or_else(|| CONFIG_DIR.to_string(), |path| path.display().to_string())
+}
+
+// Parse one tenants file, or None if it isn't a config file at all
+async fn read_tenant_file(path: &Path) -> Result<Option<TenantsConfig>, Box<dyn std::error::Error>> {
+    let value: serde_yaml::Value = match path.extension() {
+        Some(ext) if ext == "yml" || ext == "yaml" => {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            serde_yaml::from_str(&content)
+                .map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        Some(ext) if ext == "toml" => {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let value: toml::Value = toml::from_str(&content)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            serde_yaml::to_value(value)?
+        }
+        _ => return Ok(None),
+    };
+    
+    let tenant_config = interpret(value, path)?;
+    debug!("Read {} tenant(s) from {}", tenant_config.len(), path.display());
+    
+    Ok(Some(tenant_config))
+}
+
+// Tenants of one parsed file, after substituting ${VAR} references
+fn interpret(mut value: serde_yaml::Value, path: &Path) -> Result<TenantsConfig, Box<dyn std::error::Error>> {
+    expand_env(&mut value).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(serde_yaml::from_value(value).map_err(|e| format!("{}: {}", path.display(), e))?)
+}
+
+// Expand ${VAR} in every string value, "$${" is a literal "${"
+fn expand_env(value: &mut serde_yaml::Value) -> Result<(), String> {
+    match value {
+        serde_yaml::Value::String(text) if text.contains("${") => *text = expand_env_str(text)?,
+        serde_yaml::Value::Sequence(items) => {
+            for item in items {
+                expand_env(item)?;
+            }
+        }
+        serde_yaml::Value::Mapping(map) => {
+            for (_, item) in map.iter_mut() {
+                expand_env(item)?;
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) => expand_env(&mut tagged.value)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn expand_env_str(text: &str) -> Result<String, String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            expanded.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = rest.strip_prefix("${") {
+            let end = reference
+                .find('}')
+                .ok_or_else(|| format!("Unterminated variable reference in '{}'", text))?;
+            let name = &reference[..end];
+            let value = std::env::var(name)
+                .map_err(|_| format!("Environment variable '{}' is not set", name))?;
+            expanded.push_str(&value);
+            rest = &reference[end + 1..];
+        } else {
+            expanded.push('$');
+            rest = &rest[1..];
+        }
+    }
+    
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn validate_tenants(tenants: &LoadedTenants, urn_aliases: &URNAliases) -> Vec<String> {
+    let mut errors = Vec::new();
+    
+    for (tenant_name, (path, tenant_config)) in tenants {
+        let context = format!("tenant '{}' ({})", tenant_name, path.display());
+        let urn_aliases = &tenant_config.merged_aliases(urn_aliases);
+        if tenant_config.all_domains().is_empty() {
+            errors.push(format!("{}: must set 'domain' or 'domains'", context));
+        }
+        
+        for (user_id, user_data) in &tenant_config.users {
+            if let Err(e) = normalize_subject(user_id) {
+                errors.push(format!("{}: {}", context, e));
+            }
+            
+            let context = format!("{}, user '{}'", context, user_id);
+            if let Some(subject) = &user_data.subject {
+                if Url::parse(subject).is_err() {
+                    errors.push(format!("{}: subject '{}' is not an absolute URI", context, subject));
+                }
+            }
+            for alias in &user_data.aliases {
+                if Url::parse(alias).is_err() {
+                    errors.push(format!("{}: alias '{}' is not an absolute URI", context, alias));
+                }
+            }
+            for other in &user_data.also_known_as {
+                if Url::parse(other).is_err() {
+                    errors.push(format!("{}: also_known_as '{}' is not an absolute URI", context, other));
+                }
+            }
+            if let Some(target) = &user_data.redirect_to {
+                if !target.starts_with("acct:") || !target.contains('@') || Url::parse(target).is_err() {
+                    errors.push(format!("{}: redirect_to '{}' is not an acct: URI", context, target));
+                } else if normalize_subject(user_id).is_ok_and(|key| key == normalize_acct(target)) {
+                    errors.push(format!("{}: redirect_to points at the user itself", context));
+                }
+            }
+            validate_entries(&context, &user_data.entries, urn_aliases, &mut errors);
+            validate_entries(&context, &user_data.links, urn_aliases, &mut errors);
+            for (key, value) in &user_data.links {
+                match value {
+                    UserValue::Text(href) if Url::parse(href).is_err() => {
+                        errors.push(format!("{}: href '{}' for '{}' is not an absolute URL", context, href, key));
+                    }
+                    UserValue::Null => errors.push(format!("{}: link '{}' has no href", context, key)),
+                    _ => {}
+                }
+            }
+            for key in user_data.properties.keys() {
+                validate_name(&context, key, urn_aliases, &mut errors);
+            }
+        }
+        
+        if let Some(openid) = &tenant_config.openid {
+            if matches!(openid, OpenIdConfig::Issuers(issuers) if issuers.is_empty()) {
+                errors.push(format!("{}: openid lists no issuers", context));
+            }
+            for (href, _) in openid.issuers() {
+                if Url::parse(href).is_err() {
+                    errors.push(format!("{}: openid '{}' is not an absolute URL", context, href));
+                }
+            }
+        }
+        if let Some(nodeinfo) = &tenant_config.nodeinfo {
+            if Url::parse(nodeinfo).is_err() {
+                errors.push(format!("{}: nodeinfo '{}' is not an absolute URL", context, nodeinfo));
+            }
+        }
+        if let Some(language) = &tenant_config.language {
+            if !LANGUAGE_TAG_REGEX.is_match(language) {
+                errors.push(format!("{}: language '{}' is not a language tag", context, language));
+            }
+        }
+        if let Some(canonical) = &tenant_config.canonical_domain {
+            if !tenant_config.all_domains().contains(&normalize_domain(canonical)) {
+                errors.push(format!("{}: canonical_domain '{}' is not one of its domains", context, canonical));
+            }
+        }
+        let templates_context = format!("{}, link_templates", context);
+        validate_entries(&templates_context, &tenant_config.link_templates, urn_aliases, &mut errors);
+        if !tenant_config.global && !tenant_config.wildcard_subdomains.is_empty() {
+            errors.push(format!("{}: wildcard_subdomains need 'global: true'", context));
+        }
+        let domains = tenant_config.all_domains();
+        for (pattern, entries) in &tenant_config.wildcard_subdomains {
+            // Below one of the tenant's domains, never the domain itself
+            let base = normalize_domain(pattern.strip_prefix("*.").unwrap_or(pattern));
+            let within = domains
+                .iter()
+                .any(|domain| (base == *domain && pattern.starts_with("*.")) || base.ends_with(&format!(".{}", domain)));
+            if !within || base.contains('*') {
+                errors.push(format!("{}: wildcard_subdomains pattern '{}' is not a subdomain of its domains", context, pattern));
+            }
+            let context = format!("{}, wildcard_subdomains '{}'", context, pattern);
+            validate_entries(&context, entries, urn_aliases, &mut errors);
+        }
+        let context = format!("{}, global_links", context);
+        validate_entries(&context, &tenant_config.global_links, urn_aliases, &mut errors);
+    }
+    
+    let mut defaults: Vec<&str> = tenants
+        .iter()
+        .filter(|(_, (_, tenant))| tenant.default)
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if defaults.len() > 1 {
+        defaults.sort();
+        errors.push(format!("Only one tenant may set 'default', found: {}", defaults.join(", ")));
+    }
+    
+    // HashMap order is random, keep the report stable between runs
+    errors.sort();
+    errors
+}
+
+// Flat entries that only became properties because they didn't parse as a URL,
+// while looking like one (containing "://" or starting with "http"). Values
+// under an explicit properties: block are taken as meant
+fn url_like_entries(tenants: &LoadedTenants) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for (tenant_name, (_, tenant_config)) in tenants {
+        let mut check = |user: &str, key: &str, value: &UserValue| {
+            if let UserValue::Text(value) = value {
+                if Url::parse(value).is_err() && (value.contains("://") || value.starts_with("http")) {
+                    warnings.push(format!(
+                        "Tenant '{}', user '{}': property '{}' looks like a URL but isn't one: {}",
+                        tenant_name, user, key, value
+                    ));
+                }
+            }
+        };
+        
+        for (user_id, user_data) in &tenant_config.users {
+            let user = normalize_subject(user_id).unwrap_or_else(|_| user_id.clone());
+            for (key, value) in &user_data.entries {
+                // The explicit sections win over a flat entry with the same key
+                if !user_data.links.contains_key(key) && !user_data.properties.contains_key(key) {
+                    check(&user, key, value);
+                }
+            }
+        }
+        for (key, value) in tenant_config.link_templates.iter().chain(&tenant_config.global_links) {
+            check("*", key, value);
+        }
+        for (pattern, entries) in &tenant_config.wildcard_subdomains {
+            for (key, value) in entries {
+                check(&format!("*@{}", pattern), key, value);
+            }
+        }
+    }
+    
+    // HashMap order is random, keep the report stable between runs
+    warnings.sort();
+    warnings
+}
+
+// Aliases no key uses, and keys that are neither an alias nor a URN some
+// alias maps to, which is where typos in either usually end up
+fn lint_tenants(tenants: &LoadedTenants, urn_aliases: &URNAliases) -> Vec<String> {
+    let mut findings = Vec::new();
+    let mut used: HashSet<&str> = HashSet::new();
+    
+    for (tenant_name, (path, tenant_config)) in tenants {
+        let context = format!("tenant '{}' ({})", tenant_name, path.display());
+        let local = &tenant_config.urn_aliases;
+        let mut used_locally: HashSet<&str> = HashSet::new();
+        // The issuer link looks up the openid alias itself
+        if tenant_config.openid.is_some() {
+            used.insert(OPENID_ALIAS);
+            used_locally.insert(OPENID_ALIAS);
+        }
+        
+        for (owner, key) in tenant_keys(tenant_config) {
+            if local.contains_key(key) {
+                used_locally.insert(key);
+            } else if urn_aliases.contains_key(key) {
+                used.insert(key);
+            } else if !local.values().chain(urn_aliases.values()).any(|urn| urn == key) {
+                findings.push(format!("{}, {}: '{}' is not an alias, and no alias maps to it", context, owner, key));
+            }
+        }
+        for alias in local.keys().filter(|alias| !used_locally.contains(alias.as_str())) {
+            findings.push(format!("{}: urn_alias '{}' is never used", context, alias));
+        }
+    }
+    
+    for alias in urn_aliases.keys().filter(|alias| !used.contains(alias.as_str())) {
+        findings.push(format!("URN alias '{}' is never used", alias));
+    }
+    findings.sort();
+    findings
+}
+
+// Every key that goes through alias resolution, with where it was found
+fn tenant_keys(tenant_config: &TenantConfig) -> Vec<(String, &str)> {
+    let mut keys = Vec::new();
+    for (user_id, user_data) in &tenant_config.users {
+        let owner = format!("user '{}'", user_id);
+        entry_keys(&owner, &user_data.entries, &mut keys);
+        entry_keys(&owner, &user_data.links, &mut keys);
+        keys.extend(user_data.properties.keys().map(|key| (owner.clone(), key.as_str())));
+    }
+    entry_keys("global_links", &tenant_config.global_links, &mut keys);
+    entry_keys("link_templates", &tenant_config.link_templates, &mut keys);
+    for entries in tenant_config.wildcard_subdomains.values() {
+        entry_keys("wildcard_subdomains", entries, &mut keys);
+    }
+    keys
+}
+
+fn entry_keys<'a>(owner: &str, entries: &'a Entries, keys: &mut Vec<(String, &'a str)>) {
+    for (key, value) in entries {
+        keys.push((owner.to_string(), key.as_str()));
+        if let UserValue::Link(link) = value {
+            keys.extend(link.rels.iter().map(|rel| (owner.to_string(), rel.as_str())));
+        }
+    }
+}
+
+fn validate_entries(
+    context: &str,
+    entries: &Entries,
+    urn_aliases: &URNAliases,
+    errors: &mut Vec<String>,
+) {
+    for (key, value) in entries {
+        match value {
+            UserValue::Link(link) => {
+                if link.rels.is_empty() {
+                    validate_name(context, key, urn_aliases, errors);
+                }
+                for rel in &link.rels {
+                    validate_name(context, rel, urn_aliases, errors);
+                }
+                if Url::parse(&link.href).is_err() {
+                    errors.push(format!("{}: href '{}' for '{}' is not an absolute URL", context, link.href, key));
+                }
+            }
+            UserValue::Text(_) | UserValue::Null => validate_name(context, key, urn_aliases, errors),
+        }
+    }
+}
+
+// Relations and property names must be URIs, or aliases that resolve to one
+fn validate_name(context: &str, name: &str, urn_aliases: &URNAliases, errors: &mut Vec<String>) {
+    if !urn_aliases.contains_key(name) && Url::parse(name).is_err() {
+        errors.push(format!(
+            "{}: '{}' is neither a URN alias (urns.yml or urn_aliases) nor an absolute URI",
+            context, name
+        ));
+    }
+}
+
 fn process_tenants(
-    tenants: TenantsConfig,
+    tenants: LoadedTenants,
     urn_aliases: URNAliases,
-) -> Result<HashMap<String, TenantData>, Box<dyn std::error::Error>> {
+) -> Result<HashMap<String, Arc<TenantData>>, Box<dyn std::error::Error>> {
     let mut tenant_map = HashMap::new();
+    let mut claimed_domains: HashMap<String, (String, PathBuf)> = HashMap::new();
     
-    for (tenant_name, tenant_config) in tenants {
+    for (tenant_name, (path, tenant_config)) in tenants {
         let mut fingers = HashMap::new();
+        let urn_aliases = tenant_config.merged_aliases(&urn_aliases);
+        let domains = tenant_config.all_domains();
+        
+        // A domain can only be served by one tenant
+        for domain in &domains {
+            if let Some((other_name, other_path)) = claimed_domains.get(domain) {
+                return Err(format!(
+                    "Domain '{}' is claimed by both tenant '{}' ({}) and tenant '{}' ({})",
+                    domain, other_name, other_path.display(), tenant_name, path.display()
+                ).into());
+            }
+            claimed_domains.insert(domain.clone(), (tenant_name.clone(), path.clone()));
+        }
+        
+        let canonical_domain = tenant_config.canonical_domain.as_deref().map(normalize_domain);
+        
+        let language = tenant_config.language.clone();
+        let title_language = language.as_deref().unwrap_or(UNDETERMINED_LANGUAGE);
+        
+        // Relative to the tenant file, and read again on every reload
+        let signing_key = match &tenant_config.signing_key {
+            Some(key_path) => {
+                let key_path = path.parent().unwrap_or(Path::new("")).join(key_path);
+                let key = signature::load_key(&key_path).map_err(|e| {
+                    format!("Failed to load signing_key for tenant '{}' ({}): {}", tenant_name, key_path.display(), e)
+                })?;
+                Some(key)
+            }
+            None => None,
+        };
+        
+        // Links every finger of the tenant gets, unless it defines the relation itself
+        let mut tenant_links = Vec::new();
+        if let Some(openid) = &tenant_config.openid {
+            let rel = urn_aliases.get(OPENID_ALIAS).map_or(OPENID_ISSUER_REL, String::as_str);
+            for (href, priority) in openid.issuers() {
+                let mut link = default_link(rel, href);
+                if let Some(priority) = priority {
+                    link.properties.insert(ISSUER_PRIORITY_PROPERTY.to_string(), Some(priority.to_string()));
+                }
+                tenant_links.push(link);
+            }
+        }
+        if let Some(nodeinfo) = &tenant_config.nodeinfo {
+            tenant_links.push(default_link(NODEINFO_REL, nodeinfo));
+        }
+        
+        // Shared entries, personalized for each user below
+        let template = UserConfig { entries: tenant_config.link_templates, ..Default::default() };
+        let template = create_webfinger(String::new(), template, &urn_aliases, title_language)?;
         
         // Process defined users for this tenant
         for (user_id, user_data) in tenant_config.users {
-            let subject = normalize_subject(&user_id)?;
-            let finger = create_webfinger(subject.clone(), user_data, &urn_aliases)?;
-            fingers.insert(subject, finger);
+            let key = normalize_subject(&user_id)?;
+            let subject = user_data.subject.clone();
+            let mut finger = create_webfinger(key.clone(), user_data, &urn_aliases, title_language)?;
+            // Templates are filled from the key, which is always an acct: URI
+            apply_template(&mut finger, &template);
+            add_default_links(&mut finger, &tenant_links);
+            if let Some(subject) = subject {
+                finger.subject = subject;
+            }
+            fingers.insert(key, Arc::new(finger));
         }
         
-        // Handle global configuration (accept any user for the domain)
-        if tenant_config.global {
-            if let Some(openid) = tenant_config.openid {
-                let global_data = [("openid".to_string(), openid)].into_iter().collect();
-                let subject = format!("acct:*@{}", tenant_config.domain);
-                let finger = create_webfinger(subject.clone(), global_data, &urn_aliases)?;
-                fingers.insert(subject, finger);
+        // Handle global configuration (accept any user for each domain)
+        if tenant_config.global && tenant_config.confirm_unknown_users {
+            let entries = tenant_config.global_links;
+            if !entries.is_empty() || !tenant_links.is_empty() {
+                let global_data = UserConfig { entries, ..Default::default() };
+                let mut finger = create_webfinger(String::new(), global_data, &urn_aliases, title_language)?;
+                add_default_links(&mut finger, &tenant_links);
+                for domain in &domains {
+                    let subject = format!("acct:*@{}", domain);
+                    let mut finger = finger.clone();
+                    finger.subject = subject.clone();
+                    fingers.insert(subject, Arc::new(finger));
+                }
             }
         }
         
+        let mut subdomain_patterns = Vec::new();
+        if tenant_config.global && tenant_config.confirm_unknown_users {
+            for (pattern, entries) in tenant_config.wildcard_subdomains {
+                let pattern = match pattern.strip_prefix("*.") {
+                    Some(base) => format!("*.{}", normalize_domain(base)),
+                    None => normalize_domain(&pattern),
+                };
+                let subject = format!("acct:*@{}", pattern);
+                let data = UserConfig { entries, ..Default::default() };
+                let mut finger = create_webfinger(subject.clone(), data, &urn_aliases, title_language)?;
+                add_default_links(&mut finger, &tenant_links);
+                fingers.insert(subject, Arc::new(finger));
+                subdomain_patterns.push(pattern);
+            }
+        }
+        // Exact names before *. patterns, longer suffixes before shorter ones
+        subdomain_patterns.sort_by_key(|pattern| (pattern.starts_with("*."), std::cmp::Reverse(pattern.len())));
+        
+        info!("Loaded tenant '{}' for domains '{}' with {} webfingers (global: {})", 
+                 tenant_name, domains.join(", "), fingers.len(), tenant_config.global);
+        
         // Create tenant data
         let tenant_data = TenantData {
-            domain: tenant_config.domain.clone(),
+            name: tenant_name.clone(),
+            domains,
             global: tenant_config.global,
+            accept_mailto: tenant_config.accept_mailto,
+            strip_plus_tags: tenant_config.strip_plus_tags,
+            language,
+            canonical_domain,
+            urn_aliases,
             fingers,
+            subdomain_patterns,
+            signing_key,
         };
         
-        println!("Loaded tenant '{}' for domain '{}' with {} webfingers (global: {})", 
-                 tenant_name, tenant_config.domain, tenant_data.fingers.len(), tenant_config.global);
-        
-        tenant_map.insert(tenant_name, tenant_data);
+        tenant_map.insert(tenant_name, Arc::new(tenant_data));
     }
     
     Ok(tenant_map)
 }
 
-fn normalize_subject(user_id: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let subject = if user_id.starts_with("acct:") {
-        user_id[5..].to_string()
-    } else {
-        user_id.to_string()
+// Merge the tenant's link_templates into a user's finger, the user's own entries win
+fn apply_template(finger: &mut WebFinger, template: &WebFinger) {
+    let Some((user, domain)) = finger.subject.strip_prefix("acct:").and_then(|address| address.rsplit_once('@')) else {
+        return;
+    };
+    
+    let mut links: Vec<Link> = template
+        .links
+        .iter()
+        .filter(|link| !finger.links.iter().any(|own| own.rel == link.rel))
+        .cloned()
+        .collect();
+    fill_placeholders(&mut links, user, domain);
+    finger.links.extend(links);
+    
+    for (key, value) in &template.properties {
+        finger.properties.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+}
+
+// Characters allowed unescaped when substituting into an href (RFC 3986 unreserved)
+const TEMPLATE_VALUE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+// Fill {user} and {domain} placeholders in link hrefs
+pub fn fill_placeholders(links: &mut [Link], user: &str, domain: &str) {
+    let user = utf8_percent_encode(user, TEMPLATE_VALUE).to_string();
+    for link in links {
+        if let Some(href) = &mut link.href {
+            if href.contains('{') {
+                *href = href.replace("{user}", &user).replace("{domain}", domain);
+            }
+        }
+    }
+}
+
+// Add tenant-wide links, skipping relations the finger already defines
+fn add_default_links(finger: &mut WebFinger, defaults: &[Link]) {
+    // Only the finger's own links replace a default, the defaults may share a rel
+    let own: HashSet<String> = finger.links.iter().map(|link| link.rel.clone()).collect();
+    finger.links.extend(defaults.iter().filter(|link| !own.contains(&link.rel)).cloned());
+}
+
+fn default_link(rel: &str, href: &str) -> Link {
+    Link {
+        rel: rel.to_string(),
+        href: Some(href.to_string()),
+        template: None,
+        titles: IndexMap::new(),
+        properties: IndexMap::new(),
+    }
+}
+
+// Hostnames are case-insensitive, "example.com." is the same host as "example.com",
+// and Unicode names are compared in their punycode form ("café.example" -> "xn--caf-dma.example")
+pub fn normalize_domain(domain: &str) -> String {
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    idna::domain_to_ascii(domain).unwrap_or_else(|_| domain.to_ascii_lowercase())
+}
+
+// Normalize the host of an acct: URI so it matches configured subjects
+pub fn normalize_acct(resource: &str) -> String {
+    match resource.strip_prefix("acct:").and_then(|address| address.rsplit_once('@')) {
+        Some((user, host)) => format!("acct:{}@{}", user, normalize_domain(host)),
+        None => resource.to_string(),
+    }
+}
+
+pub(crate) fn normalize_subject(user_id: &str) -> Result<String, Box<dyn std::error::Error>> {
+    // Same NFC form that incoming resources are normalized to
+    let subject: String = user_id.strip_prefix("acct:").unwrap_or(user_id).nfc().collect();
+    let subject = match subject.rsplit_once('@') {
+        Some((user, host)) if Url::parse(&subject).is_err() => format!("{}@{}", user, normalize_domain(host)),
+        _ => subject,
     };
     
     // Validate as email or URL
-    let email_regex = Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
-    if email_regex.is_match(&subject) {
+    if EMAIL_REGEX.is_match(&subject) {
         Ok(format!("acct:{}", subject))
     } else if Url::parse(&subject).is_ok() {
         Ok(subject)
@@ -155,30 +1105,128 @@ fn normalize_subject(user_id: &str) -> Result<String, Box<dyn std::error::Error>
 
 fn create_webfinger(
     subject: String,
-    user_data: HashMap<String, String>,
+    user_data: UserConfig,
     urn_aliases: &URNAliases,
+    language: &str,
 ) -> Result<WebFinger, Box<dyn std::error::Error>> {
     let mut links = Vec::new();
-    let mut properties = HashMap::new();
+    let mut properties = IndexMap::new();
     
-    for (key, value) in user_data {
+    // Aliases must be absolute URIs (RFC 7033 section 4.4.2)
+    for alias in &user_data.aliases {
+        if Url::parse(alias).is_err() {
+            return Err(format!("Invalid alias for '{}': {}", subject, alias).into());
+        }
+    }
+    
+    for (key, value) in user_data.entries {
+        // The explicit sections win over a flat entry with the same key
+        if user_data.links.contains_key(&key) || user_data.properties.contains_key(&key) {
+            continue;
+        }
+        
         // Resolve URN alias if exists
-        let urn = urn_aliases.get(&key).cloned().unwrap_or(key);
-        
-        // Check if value is a valid URL (add to links) or property
-        if Url::parse(&value).is_ok() {
-            links.push(Link {
-                rel: urn,
-                href: Some(value),
-            });
-        } else {
-            properties.insert(urn, value);
+        let urn = resolve_urn(key, urn_aliases);
+        
+        match value {
+            // Structured entries are always links
+            UserValue::Link(link) => push_link(&mut links, urn, link, urn_aliases, language)?,
+            // Check if value is a valid URL (add to links) or property
+            UserValue::Text(value) => {
+                if Url::parse(&value).is_ok() {
+                    links.push(Link {
+                        rel: urn,
+                        href: Some(value),
+                        template: None,
+                        titles: IndexMap::new(),
+                        properties: IndexMap::new(),
+                    });
+                } else {
+                    properties.insert(urn, Some(value));
+                }
+            }
+            UserValue::Null => {
+                properties.insert(urn, None);
+            }
+        }
+    }
+    
+    // Anything under links: is a link, a plain value being its href
+    for (key, value) in user_data.links {
+        let link = match value {
+            UserValue::Link(link) => link,
+            UserValue::Text(href) => LinkConfig { href, ..Default::default() },
+            UserValue::Null => return Err(format!("Link '{}' for '{}' has no href", key, subject).into()),
+        };
+        push_link(&mut links, resolve_urn(key, urn_aliases), link, urn_aliases, language)?;
+    }
+    
+    for (key, value) in user_data.properties {
+        properties.insert(resolve_urn(key, urn_aliases), value);
+    }
+    
+    // Each other identity is both an alias and a link clients can follow
+    let mut aliases = user_data.aliases;
+    for other in user_data.also_known_as {
+        if Url::parse(&other).is_err() {
+            return Err(format!("Invalid also_known_as for '{}': {}", subject, other).into());
+        }
+        links.push(Link {
+            rel: ALSO_KNOWN_AS_REL.to_string(),
+            href: Some(other.clone()),
+            template: None,
+            titles: IndexMap::new(),
+            properties: IndexMap::new(),
+        });
+        if !aliases.contains(&other) {
+            aliases.push(other);
         }
     }
     
     Ok(WebFinger {
         subject,
+        aliases,
         links,
         properties,
+        redirect_to: user_data.redirect_to.as_deref().map(normalize_acct),
     })
 }
+
+fn push_link(
+    links: &mut Vec<Link>,
+    urn: String,
+    mut link: LinkConfig,
+    urn_aliases: &URNAliases,
+    language: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if Url::parse(&link.href).is_err() {
+        return Err(format!("Invalid href for link '{}': {}", urn, link.href).into());
+    }
+    
+    // An explicitly tagged title wins over the untagged one
+    if let Some(title) = link.title.take() {
+        link.titles.entry(language.to_string()).or_insert(title);
+    }
+    
+    // One link per relation, all sharing the same href
+    let rels = if link.rels.is_empty() {
+        vec![urn]
+    } else {
+        link.rels.into_iter().map(|rel| resolve_urn(rel, urn_aliases)).collect()
+    };
+    for rel in rels {
+        links.push(Link {
+            rel,
+            href: Some(link.href.clone()),
+            template: None,
+            titles: link.titles.clone(),
+            properties: link.properties.clone(),
+        });
+    }
+    
+    Ok(())
+}
+
+fn resolve_urn(key: String, urn_aliases: &URNAliases) -> String {
+    urn_aliases.get(&key).cloned().unwrap_or(key)
+}
@@ -0,0 +1,33 @@
+use axum::{
+    http::{header, Method, StatusCode},
+    response::Response,
+};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::settings::Settings;
+
+// Discovery is read-only, so only GET and HEAD are ever allowed cross-origin.
+// Without an allowlist any origin may read responses (RFC 7033 section 5)
+pub fn layer(settings: &Settings) -> CorsLayer {
+    let origins = if settings.cors_allow_origins.is_empty() {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(settings.cors_allow_origins.iter().cloned())
+    };
+    
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods([Method::GET, Method::HEAD])
+        .allow_headers(Any)
+        .expose_headers(Any)
+        .max_age(settings.cors_max_age)
+}
+
+// The CORS layer answers preflights itself, before any lookup, but with a
+// 200. Only preflight responses carry Access-Control-Allow-Methods
+pub async fn no_content_preflight(mut response: Response) -> Response {
+    if response.status() == StatusCode::OK && response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_METHODS) {
+        *response.status_mut() = StatusCode::NO_CONTENT;
+    }
+    response
+}
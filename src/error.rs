@@ -0,0 +1,169 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use crate::telemetry::Outcome;
+
+// Machine-readable failures returned by the WebFinger endpoints
+#[derive(Debug)]
+pub enum WebFingerError {
+    UnknownDomain { domain: String },
+    ResourceNotFound { resource: String, domain: String },
+    MalformedResource { resource: String },
+    MissingResource,
+    MultipleResources,
+    TooManyResources,
+    MissingHost,
+    ResourceTooLong,
+    RateLimited { retry_after: u64 },
+    Maintenance { retry_after: u64 },
+    MethodNotAllowed,
+    // A store backend that failed, rather than found nothing
+    StoreUnavailable,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resource: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    domain: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'static str>,
+}
+
+// Error entry for a batch response, where there is no status line to carry it
+#[derive(Serialize)]
+pub struct BatchError<'a> {
+    #[serde(flatten)]
+    body: ErrorBody<'a>,
+    status: u16,
+}
+
+impl WebFingerError {
+    fn status(&self) -> StatusCode {
+        match self {
+            WebFingerError::UnknownDomain { .. } => StatusCode::NOT_FOUND,
+            WebFingerError::ResourceNotFound { .. } => StatusCode::NOT_FOUND,
+            WebFingerError::MalformedResource { .. } => StatusCode::BAD_REQUEST,
+            WebFingerError::MissingResource => StatusCode::BAD_REQUEST,
+            WebFingerError::MultipleResources => StatusCode::BAD_REQUEST,
+            WebFingerError::TooManyResources => StatusCode::BAD_REQUEST,
+            WebFingerError::MissingHost => StatusCode::BAD_REQUEST,
+            WebFingerError::ResourceTooLong => StatusCode::URI_TOO_LONG,
+            WebFingerError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            WebFingerError::Maintenance { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            WebFingerError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            WebFingerError::StoreUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+    
+    // Outcome label for the request metrics
+    pub fn outcome(&self) -> Outcome {
+        match self.status() {
+            StatusCode::BAD_REQUEST | StatusCode::URI_TOO_LONG => Outcome::BadRequest,
+            _ => Outcome::Miss,
+        }
+    }
+    
+    pub fn batch_entry(&self) -> BatchError<'_> {
+        BatchError { body: self.body(), status: self.status().as_u16() }
+    }
+    
+    fn body(&self) -> ErrorBody<'_> {
+        match self {
+            WebFingerError::UnknownDomain { domain } => ErrorBody {
+                error: "unknown_domain",
+                resource: None,
+                domain: Some(domain),
+                message: None,
+            },
+            WebFingerError::ResourceNotFound { resource, domain } => ErrorBody {
+                error: "resource_not_found",
+                resource: Some(resource),
+                domain: Some(domain),
+                message: None,
+            },
+            WebFingerError::MalformedResource { resource } => ErrorBody {
+                error: "malformed_resource",
+                resource: Some(resource),
+                domain: None,
+                message: Some("The 'resource' must be an absolute URI, e.g. acct:user@example.com"),
+            },
+            WebFingerError::MissingResource => ErrorBody {
+                error: "missing_resource",
+                resource: None,
+                domain: None,
+                message: Some("The 'resource' query parameter is required (RFC 7033 section 4.2)"),
+            },
+            WebFingerError::MultipleResources => ErrorBody {
+                error: "multiple_resources",
+                resource: None,
+                domain: None,
+                message: Some("Repeat 'resource' only together with batch=1"),
+            },
+            WebFingerError::TooManyResources => ErrorBody {
+                error: "too_many_resources",
+                resource: None,
+                domain: None,
+                message: Some("A batch=1 request holds more resources than this server accepts"),
+            },
+            WebFingerError::MissingHost => ErrorBody {
+                error: "missing_host",
+                resource: None,
+                domain: None,
+                message: Some("A Host header is required to pick the domain to answer for"),
+            },
+            WebFingerError::ResourceTooLong => ErrorBody {
+                error: "resource_too_long",
+                resource: None,
+                domain: None,
+                message: Some("The 'resource' is longer than this server accepts"),
+            },
+            WebFingerError::RateLimited { .. } => ErrorBody {
+                error: "rate_limited",
+                resource: None,
+                domain: None,
+                message: None,
+            },
+            WebFingerError::Maintenance { .. } => ErrorBody {
+                error: "maintenance",
+                resource: None,
+                domain: None,
+                message: Some("Temporarily down for maintenance"),
+            },
+            WebFingerError::MethodNotAllowed => ErrorBody {
+                error: "method_not_allowed",
+                resource: None,
+                domain: None,
+                message: Some("Discovery endpoints only support GET and HEAD"),
+            },
+            WebFingerError::StoreUnavailable => ErrorBody {
+                error: "store_unavailable",
+                resource: None,
+                domain: None,
+                message: Some("Lookups are temporarily failing, try again later"),
+            },
+        }
+    }
+}
+
+impl IntoResponse for WebFingerError {
+    fn into_response(self) -> Response {
+        let mut response = (self.status(), Json(self.body())).into_response();
+        
+        // Keep caches from holding on to errors (404 is cacheable by default)
+        response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        if let WebFingerError::RateLimited { retry_after } | WebFingerError::Maintenance { retry_after } = self {
+            response.headers_mut().insert(header::RETRY_AFTER, retry_after.into());
+        }
+        if let WebFingerError::MethodNotAllowed = self {
+            response.headers_mut().insert(header::ALLOW, HeaderValue::from_static("GET, HEAD"));
+        }
+        response
+    }
+}
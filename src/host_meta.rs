@@ -0,0 +1,65 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Uri},
+    response::IntoResponse,
+};
+use indexmap::IndexMap;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::jrd::Jrd;
+use crate::config::{normalize_domain, Config, Link};
+use crate::error::WebFingerError;
+use crate::settings::Settings;
+use crate::xrd::{self, XRD_CONTENT_TYPE};
+use crate::{request_domain, AppState};
+
+#[derive(Serialize)]
+struct HostMeta {
+    links: Vec<Link>,
+}
+
+// Host-meta document (RFC 6415) as XRD
+pub async fn xrd_handler(
+    headers: HeaderMap,
+    uri: Uri,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, WebFingerError> {
+    let link = lrdd_link(&headers, &uri, &state.config.load(), &state.settings)?;
+    let body = xrd::render(None, &[], &IndexMap::new(), &[link]);
+    
+    Ok(([(header::CONTENT_TYPE, HeaderValue::from_static(XRD_CONTENT_TYPE))], body))
+}
+
+// Host-meta document (RFC 6415) as JRD
+pub async fn jrd_handler(
+    headers: HeaderMap,
+    uri: Uri,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, WebFingerError> {
+    let link = lrdd_link(&headers, &uri, &state.config.load(), &state.settings)?;
+    Ok(Jrd(HostMeta { links: vec![link] }))
+}
+
+fn lrdd_link(
+    headers: &HeaderMap,
+    uri: &Uri,
+    config: &Config,
+    settings: &Settings,
+) -> Result<Link, WebFingerError> {
+    let domain = normalize_domain(request_domain(headers, uri, settings)?);
+    
+    // Only advertise WebFinger for domains we actually serve
+    if config.tenant_for_domain(&domain).is_none() {
+        warn!("No tenant found for host-meta domain: {}", domain);
+        return Err(WebFingerError::UnknownDomain { domain });
+    }
+    
+    Ok(Link {
+        rel: "lrdd".to_string(),
+        href: None,
+        template: Some(format!("https://{}/.well-known/webfinger?resource={{uri}}", domain)),
+        titles: IndexMap::new(),
+        properties: IndexMap::new(),
+    })
+}
@@ -0,0 +1,171 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use url::Url;
+
+use crate::config::{self, Config, URNAliases, ValidationErrors};
+
+// One link of one user: a CSV record under a subject,rel,href header, or an
+// object of a JSON list
+#[derive(Deserialize)]
+struct Row {
+    subject: String,
+    rel: String,
+    href: String,
+}
+
+#[derive(Serialize)]
+struct Tenant {
+    domain: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    domains: Vec<String>,
+    users: IndexMap<String, User>,
+}
+
+// Under links:, so no href is ever guessed to be a property
+#[derive(Default, Serialize)]
+struct User {
+    links: IndexMap<String, String>,
+}
+
+pub struct Import {
+    pub tenant: String,
+    pub yaml: String,
+    pub users: usize,
+    // Where each rejected row is in the file, and why
+    pub rejected: Vec<String>,
+}
+
+// A tenant file named after `path` with every row that passes the checks
+// a loaded config gets. Each row goes through the loader on its own, for its
+// own reason, and the whole result is loaded once more before it's returned
+pub fn import(path: &Path, content: &str, urn_aliases: &URNAliases) -> Result<Import, Box<dyn std::error::Error>> {
+    let name = path.file_stem().map_or_else(|| "imported".into(), |stem| stem.to_string_lossy());
+    let mut rejected = Vec::new();
+    let mut domains: Vec<String> = Vec::new();
+    let mut users: IndexMap<String, User> = IndexMap::new();
+    
+    for (position, row) in read_rows(path, content)? {
+        let checked = row.and_then(|row| check_row(&name, path, &row, urn_aliases).map(|checked| (row, checked)));
+        let (row, (key, domain)) = match checked {
+            Ok(checked) => checked,
+            Err(e) => {
+                rejected.push(format!("{}: {}", position, e));
+                continue;
+            }
+        };
+        let user = users.entry(key).or_default();
+        if user.links.contains_key(&row.rel) {
+            rejected.push(format!("{}: '{}' already has a link with rel '{}'", position, row.subject, row.rel));
+            continue;
+        }
+        user.links.insert(row.rel, row.href);
+        if !domains.contains(&domain) {
+            domains.push(domain);
+        }
+    }
+    
+    if users.is_empty() {
+        return Err(format!("{}: no rows to import", path.display()).into());
+    }
+    let count = users.len();
+    let users = users.into_iter().map(|(key, user)| (user_key(&key), user)).collect();
+    let tenant = Tenant { domain: domains.remove(0), domains, users };
+    let yaml = to_yaml(&name, tenant)?;
+    Config::from_yaml_at(&yaml, path, urn_aliases.clone())?;
+    
+    Ok(Import { tenant: name.into_owned(), yaml, users: count, rejected })
+}
+
+// A row and where it is in the file, e.g. "line 4", or why it didn't parse
+type Parsed = (String, Result<Row, String>);
+
+// Rows with their position in the file, each parsed on its own
+fn read_rows(path: &Path, content: &str) -> Result<Vec<Parsed>, Box<dyn std::error::Error>> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => {
+            let mut reader = csv::Reader::from_reader(content.as_bytes());
+            let headers = reader.headers()?.clone();
+            Ok(reader
+                .records()
+                .map(|record| {
+                    let line = match &record {
+                        Ok(record) => record.position(),
+                        Err(e) => e.position(),
+                    }
+                    .map_or(0, |position| position.line());
+                    let row = record.and_then(|record| record.deserialize(Some(&headers)));
+                    (format!("line {}", line), row.map_err(|e| e.to_string()))
+                })
+                .collect())
+        }
+        Some("json") => {
+            let entries: Vec<serde_json::Value> =
+                serde_json::from_str(content).map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok(entries
+                .into_iter()
+                .enumerate()
+                .map(|(index, entry)| {
+                    (format!("entry {}", index + 1), serde_json::from_value(entry).map_err(|e| e.to_string()))
+                })
+                .collect())
+        }
+        _ => Err(format!("{}: expected a .csv or .json file", path.display()).into()),
+    }
+}
+
+// The key and domain a row's user is stored under, after loading the row as
+// a tenant of its own, or every problem the loader found with it
+fn check_row(name: &str, path: &Path, row: &Row, urn_aliases: &URNAliases) -> Result<(String, String), String> {
+    let key = config::normalize_subject(&row.subject).map_err(|e| e.to_string())?;
+    let domain = subject_domain(&key).ok_or_else(|| format!("subject '{}' names no domain", row.subject))?;
+    
+    let user = User { links: IndexMap::from([(row.rel.clone(), row.href.clone())]) };
+    let tenant = Tenant { domain: domain.clone(), domains: Vec::new(), users: IndexMap::from([(user_key(&key), user)]) };
+    let yaml = to_yaml(name, tenant).map_err(|e| e.to_string())?;
+    match Config::from_yaml_at(&yaml, path, urn_aliases.clone()) {
+        Ok(_) => Ok((key, domain)),
+        Err(e) => Err(match e.downcast_ref::<ValidationErrors>() {
+            Some(errors) => errors.0.join("; "),
+            None => e.to_string(),
+        }),
+    }
+}
+
+// "example.com" for acct:bob@example.com and https://example.com/bob
+fn subject_domain(key: &str) -> Option<String> {
+    match key.strip_prefix("acct:") {
+        Some(address) => address.rsplit_once('@').map(|(_, domain)| domain.to_string()),
+        None => Url::parse(key).ok()?.host_str().map(config::normalize_domain),
+    }
+}
+
+// Tenant files spell acct: users as bare addresses
+fn user_key(key: &str) -> String {
+    key.strip_prefix("acct:").unwrap_or(key).to_string()
+}
+
+fn to_yaml(name: &str, tenant: Tenant) -> Result<String, serde_yaml::Error> {
+    let mut value = serde_yaml::to_value(IndexMap::from([(name, tenant)]))?;
+    escape_env(&mut value);
+    serde_yaml::to_string(&value)
+}
+
+// Loading expands ${VAR}, so a "${" in the imported data is written as "$${"
+// to come back out as itself
+fn escape_env(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::String(text) => *text = text.replace("${", "$${"),
+        serde_yaml::Value::Mapping(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(mut key, mut item)| {
+                    escape_env(&mut key);
+                    escape_env(&mut item);
+                    (key, item)
+                })
+                .collect();
+        }
+        _ => {}
+    }
+}
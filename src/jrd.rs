@@ -0,0 +1,82 @@
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+pub const JRD_CONTENT_TYPE: &str = "application/jrd+json";
+
+// JSON response served with the WebFinger content type (RFC 7033 section 10.2)
+pub struct Jrd<T>(pub T);
+
+impl<T: Serialize> Jrd<T> {
+    pub fn to_bytes(&self) -> Result<Bytes, serde_json::Error> {
+        serde_json::to_vec(&self.0).map(Bytes::from)
+    }
+}
+
+impl<T: Serialize> IntoResponse for Jrd<T> {
+    fn into_response(self) -> Response {
+        match self.to_bytes() {
+            Ok(body) => jrd_response(body),
+            Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
+
+// Response for an already serialized JRD body
+pub fn jrd_response(body: Bytes) -> Response {
+    (
+        [(header::CONTENT_TYPE, HeaderValue::from_static(JRD_CONTENT_TYPE))],
+        body,
+    )
+        .into_response()
+}
+
+// Discovery response with caching headers, or 304 when the client's copy is current
+pub fn cacheable_response(
+    request_headers: &HeaderMap,
+    body: Bytes,
+    content_type: &'static str,
+    max_age: u64,
+) -> Response {
+    let etag = etag_for(&body);
+    let cache_control = format!("public, max-age={}", max_age);
+    
+    let mut response = if if_none_match(request_headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        ([(header::CONTENT_TYPE, HeaderValue::from_static(content_type))], body).into_response()
+    };
+    
+    let headers = response.headers_mut();
+    // The body depends on content negotiation
+    headers.insert(header::VARY, HeaderValue::from_static("accept"));
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&cache_control) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+    response
+}
+
+// Weak validator derived from the serialized body, since the bytes on the
+// wire differ once the response is compressed
+fn etag_for(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+fn if_none_match(request_headers: &HeaderMap, etag: &str) -> bool {
+    request_headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag.trim_start_matches("W/"))
+}
@@ -0,0 +1,564 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use axum_extra::extract::Query;
+use arc_swap::ArcSwap;
+use serde::Deserialize;
+use percent_encoding::percent_decode_str;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use tower_http::timeout::TimeoutLayer;
+use tracing::{debug, field, warn, Span};
+use unicode_normalization::UnicodeNormalization;
+use url::Url;
+
+mod access_log;
+mod batch;
+mod cache;
+mod cli;
+mod compression;
+pub mod config;
+mod cors;
+pub mod error;
+mod host_meta;
+mod import;
+mod jrd;
+mod listen;
+mod log;
+mod rate_limit;
+mod reload;
+mod request_id;
+mod server;
+pub mod settings;
+mod signature;
+mod stats;
+pub mod store;
+mod telemetry;
+mod tls;
+mod watch;
+mod xrd;
+
+#[cfg(test)]
+mod tests;
+
+// The core for embedding in another service: load a Config, then either
+// resolve() resources directly or mount router() with an AppState
+pub use access_log::AccessLogFormat;
+pub use config::{normalize_domain, Config, Link, TenantData, URNAliases, WebFinger};
+pub use error::WebFingerError;
+pub use server::run;
+pub use settings::Settings;
+pub use store::{FingerStore, StaticStore};
+pub use telemetry::Outcome;
+
+use cache::{CacheKey, ResponseCache};
+use jrd::{cacheable_response, Jrd};
+use rate_limit::RateLimiter;
+use store::Lookup;
+use xrd::Format;
+
+#[derive(Deserialize)]
+struct WebFingerQuery {
+    // Defaulted so a missing value gets our own 400 instead of axum's rejection
+    #[serde(default)]
+    resource: Vec<String>,
+    #[serde(default)]
+    rel: Vec<String>,
+    // Non-standard opt-in for answering several resources at once
+    #[serde(default)]
+    batch: Option<String>,
+    // Non-standard ?minimal=1, answering with only subject and links
+    #[serde(default)]
+    minimal: Option<String>,
+}
+
+type SharedConfig = Arc<ArcSwap<Config>>;
+
+// Everything the routes share, cheap to clone
+#[derive(Clone)]
+pub struct AppState {
+    config: SharedConfig,
+    settings: Arc<Settings>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    // Disabled when CACHE_TTL_SECS is 0
+    cache: Option<Arc<ResponseCache>>,
+    // MAINTENANCE=1 or MAINTENANCE_FILE, re-checked on every reload
+    maintenance: Arc<AtomicBool>,
+    // Where fingers are looked up, the config files unless replaced
+    store: Arc<dyn FingerStore>,
+    // Requests currently being handled, reported at shutdown
+    in_flight: Arc<AtomicUsize>,
+    // For the uptime in /version
+    started: Instant,
+}
+
+impl AppState {
+    pub fn new(config: Config, settings: Settings) -> Self {
+        let config: SharedConfig = Arc::new(ArcSwap::from_pointee(config));
+        AppState {
+            store: Arc::new(StaticStore::new(config.clone())),
+            config,
+            rate_limiter: settings.rate_limit.as_ref().map(|rl| Arc::new(RateLimiter::new(rl))),
+            cache: (!settings.cache_ttl.is_zero()).then(|| Arc::new(ResponseCache::new(settings.cache_ttl))),
+            maintenance: Arc::new(AtomicBool::new(settings.maintenance())),
+            settings: Arc::new(settings),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            started: Instant::now(),
+        }
+    }
+    
+    // Look fingers up somewhere other than the config files
+    pub fn with_store(self, store: Arc<dyn FingerStore>) -> Self {
+        AppState { store, ..self }
+    }
+}
+
+// All routes of the standalone server, to serve or merge into another app
+pub fn router(state: AppState) -> Router {
+    // Public discovery routes, rate limited per client. Only these are
+    // readable cross-origin, internal endpoints get no CORS headers
+    let discovery = Router::new()
+        .route(
+            "/.well-known/webfinger",
+            get(webfinger_handler).head(webfinger_handler).fallback(method_not_allowed),
+        )
+        .route("/.well-known/host-meta", get(host_meta::xrd_handler).fallback(method_not_allowed))
+        .route("/.well-known/host-meta.json", get(host_meta::jrd_handler).fallback(method_not_allowed))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit::limit));
+    // Inside CORS so a timed out lookup is still readable cross-origin.
+    // Health checks stay outside and always answer
+    let timeout = state.settings.request_timeout;
+    let discovery = if timeout.is_zero() { discovery } else { discovery.layer(TimeoutLayer::new(timeout)) }
+        .layer(cors::layer(&state.settings))
+        .layer(middleware::map_response(cors::no_content_preflight));
+    
+    let app = Router::new()
+        .merge(discovery)
+        .route("/healthz", get(health_handler))
+        .route("/livez", get(health_handler))
+        .route("/readyz", get(ready_handler))
+        .route("/version", get(version_handler))
+        .route("/metrics", get(telemetry::metrics_handler))
+        .route("/stats", get(stats::stats_handler));
+    let app = if state.settings.strict_paths { app } else { app.fallback(well_known_redirect) };
+    
+    app
+        .layer(middleware::from_fn_with_state(state.clone(), config_hash))
+        .layer(middleware::from_fn_with_state(state.clone(), telemetry::track_in_flight))
+        .layer(middleware::from_fn(request_id::assign))
+        // Inside compression, so logged sizes are the uncompressed body
+        .layer(middleware::from_fn_with_state(state.clone(), access_log::log))
+        .layer(compression::layer(&state.settings))
+        .layer(middleware::from_fn_with_state(state.clone(), server_header))
+        .with_state(state)
+}
+
+#[tracing::instrument(name = "webfinger", skip_all, fields(resource = field::Empty, domain = field::Empty))]
+async fn webfinger_handler(
+    headers: HeaderMap,
+    uri: Uri,
+    Query(params): Query<WebFingerQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, WebFingerError> {
+    let started = Instant::now();
+    if state.maintenance.load(Ordering::Relaxed) {
+        return Err(WebFingerError::Maintenance { retry_after: state.settings.maintenance_retry_after });
+    }
+    // Oversized resources never reach normalization or the lookup maps
+    let max_len = state.settings.max_resource_len;
+    if max_len > 0 && params.resource.iter().any(|resource| resource.len() > max_len) {
+        telemetry::record_webfinger(telemetry::UNKNOWN_TENANT, Outcome::BadRequest, started);
+        return Err(WebFingerError::ResourceTooLong);
+    }
+    if batch::requested(params.batch.as_deref()) {
+        return batch::batch_response(&headers, &uri, &params, &state, started).await;
+    }
+    if params.resource.len() > 1 {
+        telemetry::record_webfinger(telemetry::UNKNOWN_TENANT, Outcome::BadRequest, started);
+        return Err(WebFingerError::MultipleResources);
+    }
+    
+    let resource = normalize_resource(params.resource.first().map(String::as_str).unwrap_or_default());
+    if resource.is_empty() {
+        debug!("WebFinger request without resource");
+        telemetry::record_webfinger(telemetry::UNKNOWN_TENANT, Outcome::BadRequest, started);
+        return Err(WebFingerError::MissingResource);
+    }
+    
+    let host = match request_domain(&headers, &uri, &state.settings) {
+        Ok(host) => normalize_domain(host),
+        Err(e) => {
+            telemetry::record_webfinger(telemetry::UNKNOWN_TENANT, Outcome::BadRequest, started);
+            return Err(e);
+        }
+    };
+    let domain = lookup_domain(&host, &resource, &state.settings);
+    
+    // Attach the request to the span so every log line carries it
+    let span = Span::current();
+    span.record("resource", resource.as_str());
+    span.record("domain", domain.as_str());
+    debug!("WebFinger request: resource={}, domain={}", resource, domain);
+    
+    let config = state.config.load();
+    let tenant = config.tenant_for_domain(&domain);
+    if let Some(redirect) = redirect_to_canonical(tenant, &domain, &host, &uri, started) {
+        return Ok(redirect);
+    }
+    let language = tenant.and_then(|tenant| tenant.language.as_deref());
+    
+    // Serve repeated lookups straight from the cache
+    let format = Format::negotiate(&headers);
+    let minimal = matches!(params.minimal.as_deref(), Some("1" | "true"));
+    let cache_key = CacheKey::new(&domain, &resource, &params.rel, format, minimal);
+    if let Some(cached) = state.cache.as_ref().and_then(|cache| cache.get(&cache_key)) {
+        telemetry::record_webfinger(&cached.tenant, cached.outcome, started);
+        let response = cacheable_response(&headers, cached.body.clone(), format.content_type(), state.settings.cache_max_age);
+        let response = with_signature(response, &cached.body, &config, &cached.tenant);
+        return Ok(with_language(response, language));
+    }
+    
+    let mut resolved = match find_finger(&state, &domain, resource, &params.rel).await {
+        Ok(resolved) => resolved,
+        Err((tenant, e)) => {
+            telemetry::record_webfinger(&tenant, e.outcome(), started);
+            return Err(e);
+        }
+    };
+    
+    // Old handles send clients on to their canonical subject
+    if let Some(target) = resolved.finger.redirect_to.as_deref() {
+        telemetry::record_webfinger(&resolved.tenant, Outcome::Redirect, started);
+        return Ok(subject_redirect(target, &uri));
+    }
+    
+    if minimal {
+        resolved.finger = Arc::new(minimal_finger(&resolved.finger));
+    }
+    
+    let body = match format {
+        Format::Jrd | Format::Json => match Jrd(resolved.finger.as_ref()).to_bytes() {
+            Ok(body) => body,
+            Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        },
+        Format::Xrd => xrd::finger_to_xrd(&resolved.finger).into(),
+    };
+    if let Some(cache) = &state.cache {
+        cache.insert(cache_key, body.clone(), &resolved.tenant, resolved.outcome);
+    }
+    
+    telemetry::record_webfinger(&resolved.tenant, resolved.outcome, started);
+    let response = cacheable_response(&headers, body.clone(), format.content_type(), state.settings.cache_max_age);
+    let response = with_signature(response, &body, &config, &resolved.tenant);
+    Ok(with_language(response, language))
+}
+
+// Signed with the key of the tenant that answered, if it has one
+fn with_signature(mut response: Response, body: &[u8], config: &Config, tenant: &str) -> Response {
+    if let Some(key) = config.tenants.get(tenant).and_then(|tenant| tenant.signing_key.as_ref()) {
+        signature::sign(&mut response, body, key, tenant);
+    }
+    response
+}
+
+// Content-Language of the tenant that answered, when it configured one
+fn with_language(mut response: Response, language: Option<&str>) -> Response {
+    if let Some(value) = language.and_then(|language| HeaderValue::from_str(language).ok()) {
+        response.headers_mut().insert(header::CONTENT_LANGUAGE, value);
+    }
+    response
+}
+
+// Find the finger for a resource through the store, then apply the rel filter
+async fn find_finger(state: &AppState, domain: &str, resource: String, rels: &[String]) -> Lookup {
+    if is_malformed(&resource) {
+        warn!("Malformed WebFinger resource: {}", resource);
+        return Err((telemetry::UNKNOWN_TENANT.to_string(), WebFingerError::MalformedResource { resource }));
+    }
+    
+    let mut found = state.store.lookup(domain, &resource).await?;
+    found.finger = filter_links(found.finger, &tenant_rels(&state.config.load(), &found.tenant, rels));
+    Ok(found)
+}
+
+// The finger the server would answer a request to `domain` with, from the
+// config alone. None wherever the server would answer with an error
+pub fn resolve(config: &Config, domain: &str, resource: &str, rels: &[String]) -> Option<WebFinger> {
+    let resource = normalize_resource(resource);
+    if is_malformed(&resource) {
+        return None;
+    }
+    let found = store::find(config, &normalize_domain(domain), &resource).ok()?;
+    Some(Arc::unwrap_or_clone(filter_links(found.finger, &tenant_rels(config, &found.tenant, rels))))
+}
+
+// Reject anything that isn't a URI with a scheme, and acct: URIs without
+// a host, which could never match - both are the client's fault
+fn is_malformed(resource: &str) -> bool {
+    Url::parse(resource).is_err() || (resource.starts_with("acct:") && extract_domain_from_resource(resource).is_none())
+}
+
+// Rel aliases come from the tenant that answered, if the config knows it
+fn tenant_rels(config: &Config, tenant: &str, rels: &[String]) -> Vec<String> {
+    match config.tenants.get(tenant) {
+        Some(tenant) => expand_rels(rels, &tenant.urn_aliases),
+        None => rels.to_vec(),
+    }
+}
+
+// Final segments of the routed discovery paths under /.well-known/
+const WELL_KNOWN_SEGMENTS: [&str; 3] = ["webfinger", "host-meta", "host-meta.json"];
+
+// Unrouted requests like /.well-known/WebFinger/ get a 308 to the routed
+// spelling, query string untouched. Anything else is a plain 404
+async fn well_known_redirect(uri: Uri) -> Response {
+    let segment = uri.path().strip_prefix("/.well-known/").map(|rest| rest.trim_end_matches('/'));
+    let Some(canonical) = segment.and_then(|segment| WELL_KNOWN_SEGMENTS.iter().find(|known| known.eq_ignore_ascii_case(segment))) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    
+    let location = match uri.query() {
+        Some(query) => format!("/.well-known/{}?{}", canonical, query),
+        None => format!("/.well-known/{}", canonical),
+    };
+    match HeaderValue::from_str(&location) {
+        Ok(location) => (StatusCode::PERMANENT_REDIRECT, [(header::LOCATION, location)]).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+// Secondary domains send clients to the tenant's canonical one, unless an
+// aggregator host picked the tenant from the resource, or the domain is one
+// the default tenant only answers for as the fallback
+fn redirect_to_canonical(
+    tenant: Option<&TenantData>,
+    domain: &str,
+    host: &str,
+    uri: &Uri,
+    started: Instant,
+) -> Option<Response> {
+    let tenant = tenant.filter(|tenant| domain == host && tenant.domains.iter().any(|own| own == domain))?;
+    let canonical = tenant.canonical_domain.as_deref().filter(|canonical| *canonical != domain)?;
+    telemetry::record_webfinger(&tenant.name, Outcome::Redirect, started);
+    Some(canonical_redirect(canonical, uri))
+}
+
+// Same request on the canonical host, query string untouched
+fn canonical_redirect(canonical: &str, uri: &Uri) -> Response {
+    let path_and_query = uri.path_and_query().map_or("/.well-known/webfinger", |pq| pq.as_str());
+    let location = format!("https://{}{}", canonical, path_and_query);
+    match HeaderValue::from_str(&location) {
+        Ok(location) => (StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, location)]).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+// A 307 to the subject a finger moved to. It keeps the method, so a HEAD stays a HEAD
+fn subject_redirect(target: &str, uri: &Uri) -> Response {
+    match HeaderValue::from_str(subject_location(target, uri).as_str()) {
+        Ok(location) => (StatusCode::TEMPORARY_REDIRECT, [(header::LOCATION, location)]).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+// The same single lookup for another subject, on that subject's host
+fn subject_location(target: &str, uri: &Uri) -> String {
+    let host = extract_domain_from_resource(target).unwrap_or_default();
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+    query.append_pair("resource", target);
+    for (key, value) in url::form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes()) {
+        if key != "resource" && key != "batch" {
+            query.append_pair(&key, &value);
+        }
+    }
+    format!("https://{}/.well-known/webfinger?{}", host, query.finish())
+}
+
+// Resolve the domain a request was addressed to, in order of precedence:
+// 1. X-Forwarded-Host (first entry), only when TRUST_FORWARDED_HEADERS is set
+// 2. Host header
+// 3. Request authority (HTTP/2 sends no Host header)
+// 4. "localhost", only when ALLOW_MISSING_HOST is set. Otherwise a request
+//    without any of the above is a 400 (RFC 9112 section 3.2)
+fn request_domain<'a>(headers: &'a HeaderMap, uri: &'a Uri, settings: &Settings) -> Result<&'a str, WebFingerError> {
+    let forwarded = settings
+        .trust_forwarded_headers
+        .then(|| headers.get("x-forwarded-host"))
+        .flatten()
+        .and_then(header_text)
+        .and_then(|h| h.split(',').next())
+        .map(str::trim)
+        .filter(|h| !h.is_empty());
+    
+    let host = forwarded
+        .or_else(|| headers.get("host").and_then(header_text).filter(|h| !h.trim().is_empty()))
+        .or_else(|| uri.host())
+        .or_else(|| settings.allow_missing_host.then_some("localhost"))
+        .ok_or(WebFingerError::MissingHost)?;
+    
+    // Remove port if present
+    Ok(host.split(':').next().unwrap_or(host))
+}
+
+// Header text as UTF-8, so a Unicode host ("café.example") isn't dropped
+// like it would be by HeaderValue::to_str, which only takes visible ASCII
+fn header_text(value: &HeaderValue) -> Option<&str> {
+    std::str::from_utf8(value.as_bytes()).ok()
+}
+
+// Tenant domain for a lookup: the request host, or on an aggregator host
+// the domain the resource names (acct:bob@example.com -> example.com)
+fn lookup_domain(host: &str, resource: &str, settings: &Settings) -> String {
+    if !settings.aggregator_hosts.iter().any(|aggregator| aggregator == host) {
+        return host.to_string();
+    }
+    extract_domain_from_resource(resource).map_or_else(|| host.to_string(), normalize_domain)
+}
+
+// Undo one extra layer of percent-encoding (e.g. "acct%3Abob%40example.com")
+// left by clients that double-encode, after axum has decoded the query once,
+// then compose to NFC so "é" matches whether it was sent as one code point or two
+fn normalize_resource(resource: &str) -> String {
+    let resource = resource.trim();
+    let resource: String = match percent_decode_str(resource).decode_utf8() {
+        Ok(decoded) => decoded.trim().nfc().collect(),
+        Err(_) => resource.nfc().collect(),
+    };
+    lowercase_scheme(resource)
+}
+
+// URI schemes are case-insensitive (RFC 3986 section 3.1), so "ACCT:bob@example.com"
+// is "acct:bob@example.com". The rest of the URI keeps its case
+fn lowercase_scheme(mut resource: String) -> String {
+    let scheme_len = match resource.find(':') {
+        Some(len) if len > 0 => len,
+        _ => return resource,
+    };
+    let scheme = &resource[..scheme_len];
+    let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    if valid {
+        resource[..scheme_len].make_ascii_lowercase();
+    }
+    resource
+}
+
+// Keep only the links whose relation was requested (RFC 7033 section 4.3), minus
+// those excluded with a leading "-" (e.g. rel=-http://openid.net/...). Without
+// any plain rel everything not excluded is kept, and exclusion wins over inclusion.
+// The finger is only copied when something is actually dropped
+fn filter_links(mut finger: Arc<WebFinger>, rels: &[String]) -> Arc<WebFinger> {
+    let (excluded, included): (Vec<&str>, Vec<&str>) = rels.iter().map(String::as_str).partition(|rel| rel.starts_with('-'));
+    let excluded: Vec<&str> = excluded.into_iter().filter_map(|rel| rel.strip_prefix('-')).collect();
+    let keep = |link: &Link| {
+        (included.is_empty() || included.contains(&link.rel.as_str())) && !excluded.contains(&link.rel.as_str())
+    };
+    
+    if !finger.links.iter().all(keep) {
+        Arc::make_mut(&mut finger).links.retain(keep);
+    }
+    finger
+}
+
+// Subject and links only, after the rel filter has run. Link titles and
+// properties are part of the link and stay
+fn minimal_finger(finger: &WebFinger) -> WebFinger {
+    WebFinger {
+        subject: finger.subject.clone(),
+        aliases: Vec::new(),
+        links: finger.links.clone(),
+        properties: Default::default(),
+        redirect_to: None,
+    }
+}
+
+// Short names from urns.yml (e.g. rel=openid) match like they do in config keys
+fn expand_rels(rels: &[String], urn_aliases: &URNAliases) -> Vec<String> {
+    let expand = |rel: &str| urn_aliases.get(rel).cloned().unwrap_or_else(|| rel.to_string());
+    rels.iter()
+        .map(|rel| match rel.strip_prefix('-') {
+            Some(excluded) => format!("-{}", expand(excluded)),
+            None => expand(rel),
+        })
+        .collect()
+}
+
+fn extract_user_from_resource(resource: &str) -> Option<&str> {
+    strip_acct_scheme(resource).and_then(|address| address.rsplit_once('@')).map(|(user, _)| user)
+}
+
+fn extract_domain_from_resource(resource: &str) -> Option<&str> {
+    strip_acct_scheme(resource).and_then(|address| address.rsplit_once('@')).map(|(_, domain)| domain)
+}
+
+// "acct:bob+fedi@example.com" -> "acct:bob@example.com"
+fn strip_plus_tag(resource: &str) -> Option<String> {
+    let (local, domain) = strip_acct_scheme(resource)?.rsplit_once('@')?;
+    let (user, _tag) = local.split_once('+')?;
+    (!user.is_empty()).then(|| format!("acct:{}@{}", user, domain))
+}
+
+// Remove an "acct:" prefix in any case
+fn strip_acct_scheme(resource: &str) -> Option<&str> {
+    let (scheme, address) = resource.split_at_checked("acct:".len())?;
+    scheme.eq_ignore_ascii_case("acct:").then_some(address)
+}
+
+// Discovery is read-only, answer anything else with an Allow header
+async fn method_not_allowed() -> WebFingerError {
+    WebFingerError::MethodNotAllowed
+}
+
+// Liveness: the process is up and serving
+async fn health_handler() -> &'static str {
+    "OK"
+}
+
+// Which build is live, for checking a rollout
+async fn version_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "commit": env!("GIT_COMMIT"),
+        "uptime_secs": state.started.elapsed().as_secs(),
+    }))
+}
+
+// Readiness: the store can answer, e.g. a config with at least one tenant is loaded
+async fn ready_handler(State(state): State<AppState>) -> (StatusCode, String) {
+    let config = state.config.load();
+    match state.store.ready() {
+        Ok(()) => (StatusCode::OK, format!("OK: {} tenants loaded, config {}", config.tenants.len(), config.hash)),
+        Err(reason) => (StatusCode::SERVICE_UNAVAILABLE, format!("Not ready: {}", reason)),
+    }
+}
+
+// Replaces whatever Server header the stack set, or removes it
+async fn server_header(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    match &state.settings.server_header {
+        Some(value) => response.headers_mut().insert(header::SERVER, value.clone()),
+        None => response.headers_mut().remove(header::SERVER),
+    };
+    response
+}
+
+const CONFIG_HASH: HeaderName = HeaderName::from_static("x-config-hash");
+
+// Every response names the config it was served from, for drift detection
+async fn config_hash(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(&state.config.load().hash) {
+        response.headers_mut().insert(CONFIG_HASH, value);
+    }
+    response
+}
@@ -0,0 +1,27 @@
+use socket2::{Domain, Socket, Type};
+use std::net::{SocketAddr, TcpListener};
+
+const BACKLOG: i32 = 1024;
+
+// Bind every address up front so a bad one aborts startup before serving.
+// IPv6 sockets are v6-only, letting `0.0.0.0` and `[::]` share a port
+// without depending on the OS dual-stack default
+pub fn bind_all(addrs: &[SocketAddr]) -> Result<Vec<TcpListener>, Box<dyn std::error::Error>> {
+    addrs
+        .iter()
+        .map(|addr| bind(*addr).map_err(|e| format!("Failed to bind {}: {}", addr, e).into()))
+        .collect()
+}
+
+fn bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(BACKLOG)?;
+    // Required by tokio and axum-server
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
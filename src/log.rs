@@ -1,16 +1,39 @@
-use tracing_subscriber::{fmt, EnvFilter};
+use std::{env, io};
+use tracing_subscriber::{fmt, fmt::writer::BoxMakeWriter, EnvFilter};
 
-pub fn init_logging() {
-    // Initialize minimal logging with environment filter
+// Our own info lines, plus warnings and errors from dependencies
+const DEFAULT_FILTER: &str = "warn,rustfinger=info";
+
+// Commands that print results to stdout keep logs on stderr
+pub fn init_logging(to_stderr: bool) {
+    // RUST_LOG overrides the default, e.g. RUST_LOG=rustfinger=debug
     let filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("info"));
+        .unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    
+    // LOG_FORMAT=json emits one JSON object per line, including the fields of every open span
+    let json = env::var("LOG_FORMAT")
+        .map(|format| format.eq_ignore_ascii_case("json"))
+        .unwrap_or(false);
+    
+    let writer = if to_stderr { BoxMakeWriter::new(io::stderr) } else { BoxMakeWriter::new(io::stdout) };
     
-    fmt()
-        .with_env_filter(filter)
-        .with_target(false)
-        .with_thread_ids(false)
-        .with_file(false)
-        .with_line_number(false)
-        .compact()
-        .init();
+    if json {
+        fmt()
+            .with_env_filter(filter)
+            .with_writer(writer)
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init();
+    } else {
+        fmt()
+            .with_env_filter(filter)
+            .with_writer(writer)
+            .with_target(false)
+            .with_thread_ids(false)
+            .with_file(false)
+            .with_line_number(false)
+            .compact()
+            .init();
+    }
 }
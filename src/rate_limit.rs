@@ -0,0 +1,104 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+use crate::batch;
+use crate::error::WebFingerError;
+use crate::settings::RateLimitSettings;
+use crate::AppState;
+
+// Prune idle buckets once the table grows past this many clients
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// Token bucket per client IP
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(settings: &RateLimitSettings) -> Self {
+        RateLimiter {
+            per_second: settings.per_second,
+            burst: settings.burst,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+    
+    // Take `cost` tokens for the client, or return how long until they are available.
+    // A cost above the burst takes a full bucket, rather than never fitting
+    pub fn check(&self, ip: IpAddr, cost: f64) -> Result<(), Duration> {
+        let cost = cost.min(self.burst);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        
+        if buckets.len() > PRUNE_THRESHOLD {
+            // A bucket that has refilled completely is the same as no bucket
+            let full_after = self.burst / self.per_second;
+            buckets.retain(|_, b| now.duration_since(b.updated).as_secs_f64() < full_after);
+        }
+        
+        let bucket = buckets.entry(ip).or_insert(Bucket { tokens: self.burst, updated: now });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.updated = now;
+        
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((cost - bucket.tokens) / self.per_second))
+        }
+    }
+}
+
+pub async fn limit(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = &state.rate_limiter else {
+        return next.run(request).await;
+    };
+    
+    let ip = client_ip(request.headers(), connect_info.map(|c| c.0), state.settings.trust_forwarded_headers);
+    if let Some(ip) = ip {
+        let cost = batch::cost(request.uri(), state.settings.batch_max_resources);
+        if let Err(retry_after) = limiter.check(ip, cost) {
+            debug!("Rate limit exceeded for {}", ip);
+            let retry_after = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            return WebFingerError::RateLimited { retry_after }.into_response();
+        }
+    }
+    
+    next.run(request).await
+}
+
+// Use the first X-Forwarded-For entry only when forwarded headers are trusted
+pub fn client_ip(headers: &HeaderMap, peer: Option<SocketAddr>, trust_forwarded: bool) -> Option<IpAddr> {
+    let forwarded = trust_forwarded
+        .then(|| headers.get("x-forwarded-for"))
+        .flatten()
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.split(',').next())
+        .and_then(|ip| ip.trim().parse().ok());
+    
+    forwarded.or_else(|| peer.map(|addr| addr.ip()))
+}
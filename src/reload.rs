@@ -0,0 +1,155 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::config::{self, Config, CONFIG_DIR};
+use crate::AppState;
+
+// Wait for editors to finish writing before reloading
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+// Re-run the config pipeline and atomically swap in the result. Strict, so a
+// half-edited file keeps the previous config serving instead of dropping its tenants
+pub async fn reload(state: &AppState) {
+    let maintenance = state.settings.maintenance();
+    if state.maintenance.swap(maintenance, Ordering::Relaxed) != maintenance {
+        info!("Maintenance mode {}", if maintenance { "enabled" } else { "disabled" });
+    }
+    
+    apply(state, Config::load_strict().await);
+}
+
+// Swap in a freshly loaded config, or keep the current one if it failed
+pub fn apply(state: &AppState, loaded: Result<Config, Box<dyn std::error::Error>>) {
+    match loaded {
+        Ok(config) => {
+            if let Err(e) = config.check_not_empty(state.settings.require_tenants) {
+                error!("Config reload failed, keeping previous config: {}", e);
+                return;
+            }
+            info!("Reloaded {} tenants, config {}", config.tenants.len(), config.hash);
+            if state.settings.validate_hrefs {
+                config.warn_url_like_properties();
+            }
+            state.config.store(Arc::new(config));
+            
+            // Cached responses may reflect the old config
+            if let Some(cache) = &state.cache {
+                cache.clear();
+            }
+        }
+        Err(e) => error!("Config reload failed, keeping previous config: {}", e),
+    }
+}
+
+// With CONFIG_FILE, watch its directory (editors replace files rather than
+// writing in place) and only react to events for that file
+pub fn config_target() -> (PathBuf, Option<OsString>) {
+    match config::config_file() {
+        Some(path) => {
+            let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            (dir.to_path_buf(), path.file_name().map(|name| name.to_os_string()))
+        }
+        None => (PathBuf::from(CONFIG_DIR), None),
+    }
+}
+
+// Send on every change in `dir`, or only to `file_name` in it when given.
+// Changes stop being reported once the watcher is dropped
+pub fn watch(
+    dir: &Path,
+    file_name: Option<OsString>,
+    tx: mpsc::UnboundedSender<()>,
+) -> Result<RecommendedWatcher, notify::Error> {
+    let mut watcher = RecommendedWatcher::new(
+        move |event: notify::Result<notify::Event>| {
+            // Reading the files during a reload must not trigger another one
+            if let Ok(event) = event {
+                let relevant = match &file_name {
+                    Some(name) => event.paths.iter().any(|path| path.file_name() == Some(name.as_os_str())),
+                    None => true,
+                };
+                if relevant && !event.kind.is_access() {
+                    let _ = tx.send(());
+                }
+            }
+        },
+        notify::Config::default(),
+    )?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+// Wait for the next change, then let a burst of events settle into one.
+// None once every sender is gone
+pub async fn next_change(rx: &mut mpsc::UnboundedReceiver<()>) -> Option<()> {
+    rx.recv().await?;
+    tokio::time::sleep(DEBOUNCE).await;
+    while rx.try_recv().is_ok() {}
+    Some(())
+}
+
+pub fn spawn_config_watcher(state: AppState) {
+    let (watch_dir, file_name) = config_target();
+    if !watch_dir.is_dir() {
+        warn!("Config directory '{}' not found, hot-reload disabled", watch_dir.display());
+        return;
+    }
+    
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let watcher = match watch(&watch_dir, file_name, tx.clone()) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("Failed to watch '{}', hot-reload disabled: {}", watch_dir.display(), e);
+            return;
+        }
+    };
+    info!("Watching '{}' for config changes", config::tenant_source());
+    
+    // Creating or removing the maintenance file reloads too
+    let maintenance_watcher = state.settings.maintenance_file.as_deref().and_then(|path| {
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        watch(dir, path.file_name().map(|name| name.to_os_string()), tx.clone())
+            .map_err(|e| error!("Failed to watch '{}', reload to apply it: {}", path.display(), e))
+            .ok()
+    });
+    
+    tokio::spawn(async move {
+        // Keep the watchers alive for as long as the task runs
+        let _watchers = (watcher, maintenance_watcher);
+        
+        while next_change(&mut rx).await.is_some() {
+            reload(&state).await;
+        }
+    });
+}
+
+// Reload on SIGHUP, for deploy scripts that signal instead of touching files
+#[cfg(unix)]
+pub fn spawn_sighup_handler(state: AppState) {
+    use tokio::signal::unix::{signal, SignalKind};
+    
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            error!("Failed to install SIGHUP handler: {}", e);
+            return;
+        }
+    };
+    
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading config");
+            reload(&state).await;
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn spawn_sighup_handler(_state: AppState) {}
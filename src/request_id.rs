@@ -0,0 +1,42 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{info_span, Instrument};
+use uuid::Uuid;
+
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    // The current request's ID, for code outside the span such as metrics
+    static REQUEST_ID: String;
+}
+
+// Longer incoming IDs are replaced rather than echoed back
+const MAX_LEN: usize = 128;
+
+// Tag every request with an ID, reusing the one our proxy sent if it looks sane
+pub async fn assign(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&X_REQUEST_ID)
+        .filter(|value| !value.is_empty() && value.len() <= MAX_LEN && value.to_str().is_ok())
+        .cloned()
+        .unwrap_or_else(|| {
+            HeaderValue::from_str(&Uuid::new_v4().to_string()).expect("UUIDs are valid header values")
+        });
+    request.headers_mut().insert(X_REQUEST_ID.clone(), request_id.clone());
+    
+    // Log lines inside the handler are nested under this span
+    let span = info_span!("request", request_id = request_id.to_str().unwrap_or_default());
+    let id = request_id.to_str().unwrap_or_default().to_string();
+    let mut response = REQUEST_ID.scope(id, next.run(request).instrument(span)).await;
+    response.headers_mut().insert(X_REQUEST_ID.clone(), request_id);
+    response
+}
+
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
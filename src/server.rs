@@ -0,0 +1,243 @@
+use std::{
+    io::{ErrorKind, Write},
+    net::SocketAddr,
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
+use tokio::signal;
+use tracing::{debug, error, info, warn};
+
+use crate::cli::{self, Command};
+use crate::config::{self, Config};
+use crate::settings::Settings;
+use crate::{import, listen, log, reload, router, store, tls, watch, AppState};
+
+// Output ending in one newline, that stops quietly once the reader is gone,
+// e.g. for `--dump-config | head`, instead of panicking like println! would
+pub(crate) fn print_to(out: &mut impl Write, text: &str) -> std::io::Result<()> {
+    match writeln!(out, "{}", text.strip_suffix('\n').unwrap_or(text)).and_then(|()| out.flush()) {
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("Shutdown signal received");
+}
+
+// Parse the command line, then check or serve the config on disk
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let command = match Command::from_args() {
+        Ok(Command::Help) => {
+            cli::print_usage();
+            return Ok(());
+        }
+        Ok(Command::PrintSchema) => {
+            print_to(&mut std::io::stdout().lock(), &config::schema())?;
+            return Ok(());
+        }
+        // Reports go to stderr, without the loader's log line for every row
+        Ok(Command::Import(path)) => {
+            let content = tokio::fs::read_to_string(&path)
+                .await
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let imported = import::import(&path, &content, &config::load_urn_aliases().await?)?;
+            for rejected in &imported.rejected {
+                eprintln!("Rejected {}", rejected);
+            }
+            eprintln!(
+                "Imported {} user(s) as tenant '{}', {} row(s) rejected",
+                imported.users, imported.tenant, imported.rejected.len()
+            );
+            print_to(&mut std::io::stdout().lock(), &imported.yaml)?;
+            if !imported.rejected.is_empty() {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    
+    // Initialize minimal logging
+    log::init_logging(matches!(command, Command::DumpConfig | Command::Watch { .. }));
+    if let Command::Watch { dump } = command {
+        return watch::run(dump).await;
+    }
+
+    let settings = Settings::from_env()?;
+    
+    // Load configuration, refusing to start with an invalid one. Validation
+    // commands never skip a file that fails to parse, whatever STRICT_CONFIG says
+    let loaded = match command {
+        Command::CheckConfig | Command::Lint => Config::load_strict().await,
+        _ => Config::load().await,
+    };
+    let config = match loaded {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = config.check_not_empty(settings.require_tenants) {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+    info!("Loaded {} tenants with {} total webfingers", 
+          config.tenants.len(),
+          config.tenants.values().map(|t| t.fingers.len()).sum::<usize>());
+    
+    // Log tenant details for debugging
+    for (name, tenant) in &config.tenants {
+        info!("Tenant '{}': domains='{}', global={}, webfingers={}", 
+              name, tenant.domains.join(", "), tenant.global, tenant.fingers.len());
+        for resource in tenant.fingers.keys() {
+            debug!("  - {}", resource);
+        }
+    }
+    
+    if let Command::CheckConfig = command {
+        info!("Configuration is valid");
+        return Ok(());
+    }
+    if let Command::DumpConfig = command {
+        print_to(&mut std::io::stdout().lock(), &config.dump()?)?;
+        return Ok(());
+    }
+    if let Command::Lint = command {
+        let findings = Config::lint().await?;
+        for finding in &findings {
+            warn!("{}", finding);
+        }
+        if !findings.is_empty() {
+            error!("{} lint issue(s) found", findings.len());
+            std::process::exit(1);
+        }
+        info!("No lint issues found");
+        return Ok(());
+    }
+
+    // Create shared state, config is swapped atomically on reload
+    if settings.validate_hrefs {
+        config.warn_url_like_properties();
+    }
+    let bind_addrs = settings.bind_addrs.clone();
+    let shutdown_timeout = settings.shutdown_timeout;
+    let state = AppState::new(config, settings);
+    #[cfg(feature = "sqlite")]
+    let state = match std::env::var("DATABASE_URL") {
+        Ok(url) if !url.is_empty() => {
+            info!("Serving fingers from {}", url);
+            state.with_store(Arc::new(store::SqliteStore::connect(&url).await?))
+        }
+        _ => state,
+    };
+    let state = match state.settings.breaker.clone() {
+        Some(breaker) => {
+            let store = Arc::new(store::CircuitBreaker::new(state.store.clone(), breaker));
+            state.with_store(store)
+        }
+        None => state,
+    };
+    reload::spawn_config_watcher(state.clone());
+    reload::spawn_sighup_handler(state.clone());
+    let in_flight = state.in_flight.clone();
+    let app = router(state);
+
+    // Load TLS material before binding so bad certificates abort startup
+    let tls_config = tls::load_tls_config().await?;
+
+    // Bind every listener, they all share the app and one shutdown signal
+    let listeners = listen::bind_all(&bind_addrs)?;
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(());
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        let _ = shutdown_tx.send(());
+    });
+    
+    let mut servers = Vec::new();
+    for listener in listeners {
+        let addr = listener.local_addr()?;
+        let app = app.clone().into_make_service_with_connect_info::<SocketAddr>();
+        let mut shutdown = shutdown_rx.clone();
+        
+        // Start each server with graceful shutdown
+        let server = match &tls_config {
+            Some(tls_config) => {
+                info!("Starting HTTPS server on {}", addr);
+                let handle = axum_server::Handle::new();
+                let shutdown_handle = handle.clone();
+                tokio::spawn(async move {
+                    let _ = shutdown.changed().await;
+                    shutdown_handle.graceful_shutdown(Some(shutdown_timeout));
+                });
+                let server = axum_server::from_tcp_rustls(listener, tls_config.clone()).handle(handle);
+                tokio::spawn(async move { server.serve(app).await })
+            }
+            None => {
+                info!("Starting server on {}", addr);
+                let listener = tokio::net::TcpListener::from_std(listener)?;
+                tokio::spawn(async move {
+                    axum::serve(listener, app)
+                        .with_graceful_shutdown(async move {
+                            let _ = shutdown.changed().await;
+                        })
+                        .await
+                })
+            }
+        };
+        servers.push(server);
+    }
+    
+    // Drain in-flight requests, but only for so long once the signal arrives
+    let drained = async {
+        for server in servers {
+            server.await??;
+        }
+        Ok::<_, Box<dyn std::error::Error>>(())
+    };
+    let mut shutdown = shutdown_rx.clone();
+    let deadline = async move {
+        let _ = shutdown.changed().await;
+        let started = Instant::now();
+        tokio::time::sleep(shutdown_timeout).await;
+        started
+    };
+    tokio::select! {
+        result = drained => result?,
+        started = deadline => warn!(
+            "Shutdown timed out after {:.1}s, closing {} requests still in flight",
+            started.elapsed().as_secs_f64(),
+            in_flight.load(Ordering::Relaxed)
+        ),
+    }
+
+    info!("Server shutdown complete, {} requests in flight", in_flight.load(Ordering::Relaxed));
+    Ok(())
+}
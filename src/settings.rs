@@ -0,0 +1,226 @@
+use axum::http::HeaderValue;
+use std::{
+    env,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::access_log::AccessLogFormat;
+use crate::config::normalize_domain;
+
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_CACHE_TTL_SECS: u64 = 300;
+const DEFAULT_CACHE_MAX_AGE_SECS: u64 = 3600;
+const DEFAULT_MAINTENANCE_RETRY_AFTER_SECS: u64 = 120;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_MAX_RESOURCE_LEN: usize = 1024;
+const DEFAULT_BATCH_MAX_RESOURCES: usize = 20;
+const DEFAULT_COMPRESSION_MIN_BYTES: u16 = 1024;
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_CORS_MAX_AGE_SECS: u64 = 86_400;
+const DEFAULT_BREAKER_FAILURES: u32 = 5;
+const DEFAULT_BREAKER_COOLDOWN_SECS: u64 = 30;
+
+// Runtime settings read from the environment at startup
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    // Addresses to listen on, each with its own server
+    pub bind_addrs: Vec<SocketAddr>,
+    pub trust_forwarded_headers: bool,
+    pub rate_limit: Option<RateLimitSettings>,
+    pub cache_ttl: Duration,
+    // Cache-Control max-age for successful responses
+    pub cache_max_age: u64,
+    // Origins allowed by CORS, any origin when empty
+    pub cors_allow_origins: Vec<HeaderValue>,
+    // How long browsers may cache a preflight answer
+    pub cors_max_age: Duration,
+    // MAINTENANCE=1, which only a restart can change
+    pub maintenance: bool,
+    // Maintenance mode while this file exists, checked on every reload
+    pub maintenance_file: Option<PathBuf>,
+    // Retry-After sent with 503s while in maintenance mode
+    pub maintenance_retry_after: u64,
+    // Warn about URL-like property values after each load
+    pub validate_hrefs: bool,
+    // Bearer token required by /stats, which answers 404 when unset
+    pub stats_token: Option<String>,
+    // How long shutdown waits for in-flight requests before closing them
+    pub shutdown_timeout: Duration,
+    // Longest resource accepted, in bytes. Zero means no limit
+    pub max_resource_len: usize,
+    // Most resources one batch=1 request may hold. Zero turns batches off
+    pub batch_max_resources: usize,
+    // Responses smaller than this are never compressed
+    pub compression_min_size: u16,
+    // Refuse to start (or reload) with zero tenants
+    pub require_tenants: bool,
+    // Discovery requests still running after this get 408. Zero disables it
+    pub request_timeout: Duration,
+    // 404 misspelled well-known paths instead of redirecting them
+    pub strict_paths: bool,
+    // Hosts that pick the tenant from the resource's domain instead of Host
+    pub aggregator_hosts: Vec<String>,
+    // Server header on every response, none when unset or empty
+    pub server_header: Option<HeaderValue>,
+    // Answer requests without a Host as localhost, for local testing
+    pub allow_missing_host: bool,
+    // Circuit breaker around the finger store, off when None
+    pub breaker: Option<BreakerSettings>,
+    // Per-request access log lines on stdout, off when None
+    pub access_log: Option<AccessLogFormat>,
+    // Replace resource values in access logged query strings
+    pub access_log_redact: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimitSettings {
+    pub per_second: f64,
+    pub burst: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct BreakerSettings {
+    // Store failures in a row that open the circuit
+    pub failures: u32,
+    // How long an open circuit answers 503 before probing the store
+    pub cooldown: Duration,
+}
+
+impl Settings {
+    // Checked at startup and again on every reload, so creating or removing
+    // MAINTENANCE_FILE flips it without a restart
+    pub fn maintenance(&self) -> bool {
+        self.maintenance || self.maintenance_file.as_ref().is_some_and(|path| path.exists())
+    }
+    
+    pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+        // Rate limiting is off unless a positive rate is configured
+        let rate_limit = match env_parse::<f64>("RATE_LIMIT_RPS")? {
+            Some(per_second) if per_second > 0.0 => {
+                let burst = env_parse::<f64>("RATE_LIMIT_BURST")?.unwrap_or(per_second).max(1.0);
+                Some(RateLimitSettings { per_second, burst })
+            }
+            _ => None,
+        };
+        
+        // Zero failures turns the breaker off
+        let breaker = match env_parse::<u32>("BREAKER_FAILURES")?.unwrap_or(DEFAULT_BREAKER_FAILURES) {
+            0 => None,
+            failures => Some(BreakerSettings {
+                failures,
+                cooldown: Duration::from_secs(
+                    env_parse("BREAKER_COOLDOWN_SECS")?.unwrap_or(DEFAULT_BREAKER_COOLDOWN_SECS),
+                ),
+            }),
+        };
+        
+        Ok(Settings {
+            bind_addrs: bind_addrs("BIND_ADDRS")?,
+            trust_forwarded_headers: env_flag("TRUST_FORWARDED_HEADERS"),
+            rate_limit,
+            cache_ttl: Duration::from_secs(env_parse("CACHE_TTL_SECS")?.unwrap_or(DEFAULT_CACHE_TTL_SECS)),
+            cache_max_age: env_parse("CACHE_MAX_AGE_SECS")?.unwrap_or(DEFAULT_CACHE_MAX_AGE_SECS),
+            cors_allow_origins: cors_origins("CORS_ALLOW_ORIGINS")?,
+            cors_max_age: Duration::from_secs(env_parse("CORS_MAX_AGE_SECS")?.unwrap_or(DEFAULT_CORS_MAX_AGE_SECS)),
+            maintenance: env_flag("MAINTENANCE"),
+            maintenance_file: env::var_os("MAINTENANCE_FILE").filter(|path| !path.is_empty()).map(PathBuf::from),
+            maintenance_retry_after: env_parse("MAINTENANCE_RETRY_AFTER_SECS")?
+                .unwrap_or(DEFAULT_MAINTENANCE_RETRY_AFTER_SECS),
+            validate_hrefs: env_flag("VALIDATE_HREFS"),
+            stats_token: env::var("STATS_TOKEN").ok().filter(|token| !token.is_empty()),
+            shutdown_timeout: Duration::from_secs(
+                env_parse("SHUTDOWN_TIMEOUT_SECS")?.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+            ),
+            max_resource_len: env_parse("MAX_RESOURCE_LEN")?.unwrap_or(DEFAULT_MAX_RESOURCE_LEN),
+            batch_max_resources: env_parse("BATCH_MAX_RESOURCES")?.unwrap_or(DEFAULT_BATCH_MAX_RESOURCES),
+            compression_min_size: env_parse("COMPRESSION_MIN_BYTES")?.unwrap_or(DEFAULT_COMPRESSION_MIN_BYTES),
+            require_tenants: env_flag("REQUIRE_TENANTS"),
+            request_timeout: Duration::from_secs(
+                env_parse("REQUEST_TIMEOUT_SECS")?.unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS),
+            ),
+            strict_paths: env_flag("STRICT_PATHS"),
+            aggregator_hosts: env::var("AGGREGATOR_HOSTS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|host| !host.is_empty())
+                .map(normalize_domain)
+                .collect(),
+            allow_missing_host: env_flag("ALLOW_MISSING_HOST"),
+            breaker,
+            access_log: match env::var("ACCESS_LOG_FORMAT").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+                "" => None,
+                "clf" | "common" => Some(AccessLogFormat::Clf),
+                "combined" => Some(AccessLogFormat::Combined),
+                other => return Err(format!("Invalid value for ACCESS_LOG_FORMAT: {}", other).into()),
+            },
+            access_log_redact: env_flag("ACCESS_LOG_REDACT"),
+            server_header: match env::var("SERVER_HEADER") {
+                Ok(value) if !value.trim().is_empty() => Some(
+                    HeaderValue::from_str(value.trim()).map_err(|_| format!("Invalid value for SERVER_HEADER: {}", value))?,
+                ),
+                _ => None,
+            },
+        })
+    }
+}
+
+// Read on every config load
+pub fn strict_config_from_env() -> bool {
+    env_flag("STRICT_CONFIG")
+}
+
+fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Result<Option<T>, Box<dyn std::error::Error>> {
+    match env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid value for {}: {}", name, value).into()),
+        _ => Ok(None),
+    }
+}
+
+// Comma-separated origins such as "https://app.example.com", matched exactly
+fn cors_origins(name: &str) -> Result<Vec<HeaderValue>, Box<dyn std::error::Error>> {
+    let Ok(value) = env::var(name) else {
+        return Ok(Vec::new());
+    };
+    
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            let origin = origin.trim_end_matches('/');
+            if !(origin.starts_with("https://") || origin.starts_with("http://")) {
+                return Err(format!("Invalid origin in {}: {}", name, origin).into());
+            }
+            HeaderValue::from_str(origin).map_err(|_| format!("Invalid origin in {}: {}", name, origin).into())
+        })
+        .collect()
+}
+
+// Comma-separated socket addresses such as "0.0.0.0:8080,[::]:8080"
+fn bind_addrs(name: &str) -> Result<Vec<SocketAddr>, Box<dyn std::error::Error>> {
+    let addrs = env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|addr| !addr.is_empty())
+        .map(|addr| addr.parse().map_err(|_| format!("Invalid address in {}: {}", name, addr).into()))
+        .collect::<Result<Vec<SocketAddr>, Box<dyn std::error::Error>>>()?;
+    
+    if addrs.is_empty() {
+        return Ok(vec![SocketAddr::from((Ipv4Addr::UNSPECIFIED, DEFAULT_PORT))]);
+    }
+    Ok(addrs)
+}
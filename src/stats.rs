@@ -0,0 +1,71 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::AppState;
+
+#[derive(Serialize)]
+struct Stats<'a> {
+    tenants: BTreeMap<&'a str, TenantStats<'a>>,
+}
+
+#[derive(Serialize)]
+struct TenantStats<'a> {
+    domains: &'a [String],
+    global: bool,
+    fingers: usize,
+    links: usize,
+    properties: usize,
+}
+
+// Per-tenant totals derived from the current config, behind STATS_TOKEN.
+// Without a token the endpoint doesn't exist, it lists every tenant
+pub async fn stats_handler(headers: HeaderMap, State(state): State<AppState>) -> Response {
+    let Some(token) = &state.settings.stats_token else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !authorized(&headers, token) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"))],
+        )
+            .into_response();
+    }
+    
+    let config = state.config.load();
+    let tenants = config
+        .tenants
+        .values()
+        .map(|tenant| {
+            let stats = TenantStats {
+                domains: &tenant.domains,
+                global: tenant.global,
+                fingers: tenant.fingers.len(),
+                links: tenant.fingers.values().map(|finger| finger.links.len()).sum(),
+                properties: tenant.fingers.values().map(|finger| finger.properties.len()).sum(),
+            };
+            (tenant.name.as_str(), stats)
+        })
+        .collect();
+    
+    Json(Stats { tenants }).into_response()
+}
+
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    let Some(provided) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    
+    // Compare without short-circuiting so timing doesn't leak the token
+    provided.len() == token.len()
+        && provided.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
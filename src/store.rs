@@ -0,0 +1,178 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+use tracing::warn;
+
+use crate::config::{fill_placeholders, normalize_acct, normalize_domain, Config, TenantData, WebFinger};
+use crate::error::WebFingerError;
+use crate::telemetry::{self, Outcome};
+use crate::{extract_domain_from_resource, extract_user_from_resource, strip_plus_tag, SharedConfig};
+
+mod breaker;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+pub use breaker::CircuitBreaker;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+// A finger found by a store, with what to record for it
+pub struct Found {
+    pub tenant: String,
+    pub outcome: Outcome,
+    pub finger: Arc<WebFinger>,
+}
+
+// Failures carry the tenant label to record as well
+pub type Lookup = Result<Found, (String, WebFingerError)>;
+
+pub type LookupFuture<'a> = Pin<Box<dyn Future<Output = Lookup> + Send + 'a>>;
+
+// Where fingers come from. Resources arrive validated and normalized, and rel
+// filtering happens afterwards, so a store only maps a resource to its finger
+pub trait FingerStore: Send + Sync {
+    fn lookup<'a>(&'a self, domain: &'a str, resource: &'a str) -> LookupFuture<'a>;
+    
+    // Whether /readyz should report the store as able to answer, or why not
+    fn ready(&self) -> Result<(), String>;
+}
+
+// Fingers from the YAML/TOML tenant files, following hot reloads
+pub struct StaticStore {
+    config: SharedConfig,
+}
+
+impl StaticStore {
+    pub fn new(config: SharedConfig) -> Self {
+        StaticStore { config }
+    }
+}
+
+// A resource's finger in a loaded config, whatever store holds that config
+pub(crate) fn find(config: &Config, domain: &str, resource: &str) -> Lookup {
+    // Find the tenant for this domain
+    let tenant = config.tenant_for_domain(domain).ok_or_else(|| {
+        warn!("No tenant found for domain: {}", domain);
+        (telemetry::UNKNOWN_TENANT.to_string(), WebFingerError::UnknownDomain { domain: domain.to_string() })
+    })?;
+    
+    // Match mailto: resources as their acct: equivalent when the tenant allows it
+    let lookup = match resource.strip_prefix("mailto:") {
+        Some(address) if tenant.accept_mailto => format!("acct:{}", address),
+        _ => resource.to_string(),
+    };
+    let mut lookup = normalize_acct(&lookup);
+    
+    // Drop a +tag the stored users don't have, when the tenant allows it
+    if tenant.strip_plus_tags && !tenant.fingers.contains_key(&lookup) {
+        if let Some(untagged) = strip_plus_tag(&lookup) {
+            lookup = untagged;
+        }
+    }
+    
+    // Look for exact user match first
+    if let Some(finger) = tenant.fingers.get(&lookup) {
+        let mut finger = finger.clone();
+        if lookup != resource && finger.subject == lookup {
+            // Answer with the subject the client asked for, unless configured otherwise
+            Arc::make_mut(&mut finger).subject = resource.to_string();
+        }
+        return Ok(Found { tenant: tenant.name.clone(), outcome: Outcome::Hit, finger });
+    }
+    
+    // Handle global domain matching for users
+    if tenant.global {
+        // Extract domain from resource (e.g., "acct:user@domain.com" -> "domain.com").
+        // It is filled into hrefs below, so anything but a hostname is a 404
+        let resource_domain = extract_domain_from_resource(&lookup)
+            .map(normalize_domain)
+            .filter(|host| is_hostname(host));
+        if let Some(resource_domain) = resource_domain {
+            let user = extract_user_from_resource(&lookup).unwrap_or_default();
+            // Users of subdomains take the most specific matching pattern
+            if let Some(subdomain) = subdomain_of(&resource_domain, domain) {
+                let finger = tenant
+                    .subdomain_patterns
+                    .iter()
+                    .find(|pattern| pattern_matches(pattern, &resource_domain))
+                    .and_then(|pattern| tenant.fingers.get(&format!("acct:*@{}", pattern)));
+                if let Some(finger) = finger {
+                    let finger = personalize(finger, resource.to_string(), user, &resource_domain, subdomain);
+                    return Ok(Found { tenant: tenant.name.clone(), outcome: Outcome::GlobalHit, finger });
+                }
+            }
+            if resource_domain == domain {
+                if let Some(finger) = wildcard_finger(tenant, domain) {
+                    // Create a personalized response for the specific user
+                    let finger = personalize(finger, resource.to_string(), user, domain, "");
+                    return Ok(Found { tenant: tenant.name.clone(), outcome: Outcome::GlobalHit, finger });
+                }
+            }
+        }
+    }
+    
+    warn!("WebFinger resource not found: {} for domain {} (tenant '{}')", resource, domain, tenant.name);
+    Err((
+        tenant.name.clone(),
+        WebFingerError::ResourceNotFound { resource: resource.to_string(), domain: domain.to_string() },
+    ))
+}
+
+impl FingerStore for StaticStore {
+    fn lookup<'a>(&'a self, domain: &'a str, resource: &'a str) -> LookupFuture<'a> {
+        Box::pin(std::future::ready(find(&self.config.load(), domain, resource)))
+    }
+    
+    fn ready(&self) -> Result<(), String> {
+        if self.config.load().tenants.is_empty() {
+            return Err("no tenants loaded".to_string());
+        }
+        Ok(())
+    }
+}
+
+// The default tenant also answers for domains it wasn't configured with,
+// using the wildcard of its first domain (placeholders are filled per request)
+fn wildcard_finger<'a>(tenant: &'a TenantData, domain: &str) -> Option<&'a Arc<WebFinger>> {
+    tenant
+        .fingers
+        .get(&format!("acct:*@{}", domain))
+        .or_else(|| tenant.fingers.get(&format!("acct:*@{}", tenant.domains.first()?)))
+}
+
+// DNS labels once in punycode form (RFC 1123), so never "evil.com/x" or "a?b"
+fn is_hostname(domain: &str) -> bool {
+    domain.len() <= 253
+        && domain.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+        })
+}
+
+// "team" for team.example.com under example.com, empty for example.com itself
+fn subdomain_of<'a>(resource_domain: &'a str, domain: &str) -> Option<&'a str> {
+    if resource_domain == domain {
+        return Some("");
+    }
+    resource_domain.strip_suffix(domain)?.strip_suffix('.').filter(|subdomain| !subdomain.is_empty())
+}
+
+// "*.example.com" matches any name below example.com, other patterns only themselves
+fn pattern_matches(pattern: &str, resource_domain: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(base) => subdomain_of(resource_domain, base).is_some_and(|subdomain| !subdomain.is_empty()),
+        None => pattern == resource_domain,
+    }
+}
+
+// Fill {user}, {domain} and {subdomain} placeholders in the wildcard finger's hrefs
+fn personalize(finger: &WebFinger, subject: String, user: &str, domain: &str, subdomain: &str) -> Arc<WebFinger> {
+    let mut personalized = finger.clone();
+    personalized.subject = subject;
+    fill_placeholders(&mut personalized.links, user, domain);
+    for link in &mut personalized.links {
+        if let Some(href) = link.href.as_mut().filter(|href| href.contains("{subdomain}")) {
+            *href = href.replace("{subdomain}", subdomain);
+        }
+    }
+    Arc::new(personalized)
+}
@@ -0,0 +1,93 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tracing::{info, warn};
+
+use super::{FingerStore, LookupFuture};
+use crate::error::WebFingerError;
+use crate::settings::BreakerSettings;
+use crate::telemetry;
+
+#[derive(Default)]
+struct BreakerState {
+    // Backend failures in a row, reset by any answer
+    failures: u32,
+    // While set and in the future, lookups fail without reaching the store
+    open_until: Option<Instant>,
+    // The one lookup let through after the cooldown, to see if the store is back
+    probe_started: Option<Instant>,
+}
+
+// Stops sending lookups to a store that keeps failing with StoreUnavailable.
+// After `failures` in a row every lookup gets 503 for `cooldown`, then a
+// single probe decides whether to close again or wait another cooldown
+pub struct CircuitBreaker {
+    inner: Arc<dyn FingerStore>,
+    settings: BreakerSettings,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(inner: Arc<dyn FingerStore>, settings: BreakerSettings) -> Self {
+        CircuitBreaker { inner, settings, state: Mutex::new(BreakerState::default()) }
+    }
+    
+    // Whether this lookup may reach the store
+    fn admit(&self) -> bool {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.open_until {
+            None => true,
+            Some(until) if now < until => false,
+            // Half-open: one probe at a time, unless the last one never finished
+            Some(_) => {
+                let probing = state.probe_started.is_some_and(|started| now < started + self.settings.cooldown);
+                if !probing {
+                    state.probe_started = Some(now);
+                }
+                !probing
+            }
+        }
+    }
+    
+    fn record(&self, failed: bool) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if !failed {
+            if state.open_until.is_some() {
+                info!("Finger store recovered, circuit closed");
+            }
+            *state = BreakerState::default();
+            return;
+        }
+        
+        state.failures += 1;
+        if state.probe_started.is_some() || state.failures >= self.settings.failures {
+            warn!(
+                "Finger store failed {} times in a row, circuit open for {}s",
+                state.failures,
+                self.settings.cooldown.as_secs()
+            );
+            state.open_until = Some(Instant::now() + self.settings.cooldown);
+            state.probe_started = None;
+        }
+    }
+}
+
+impl FingerStore for CircuitBreaker {
+    fn lookup<'a>(&'a self, domain: &'a str, resource: &'a str) -> LookupFuture<'a> {
+        Box::pin(async move {
+            if !self.admit() {
+                return Err((telemetry::UNKNOWN_TENANT.to_string(), WebFingerError::StoreUnavailable));
+            }
+            let lookup = self.inner.lookup(domain, resource).await;
+            // Only backend errors count, a 404 is a healthy answer
+            self.record(matches!(lookup, Err((_, WebFingerError::StoreUnavailable))));
+            lookup
+        })
+    }
+    
+    fn ready(&self) -> Result<(), String> {
+        self.inner.ready()
+    }
+}
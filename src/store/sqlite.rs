@@ -0,0 +1,110 @@
+use indexmap::IndexMap;
+use sqlx::{sqlite::SqlitePool, Row};
+use std::sync::Arc;
+use tracing::error;
+
+use super::{FingerStore, Found, Lookup, LookupFuture};
+use crate::config::{fill_placeholders, normalize_domain, Link, WebFinger};
+use crate::error::WebFingerError;
+use crate::telemetry::Outcome;
+use crate::{extract_domain_from_resource, extract_user_from_resource};
+
+// Tenant label for lookups answered by the database
+const TENANT: &str = "sqlite";
+
+// Fingers from the `fingers` table, see migrations/
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    // Connects to DATABASE_URL and brings the schema up to date
+    pub async fn connect(url: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let pool = SqlitePool::connect(url).await?;
+        sqlx::migrate!("./migrations").run(&pool).await?;
+        Ok(SqliteStore { pool })
+    }
+    
+    async fn finger(&self, domain: &str, subject: &str) -> Result<Option<WebFinger>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT rel, href, property_key, property_value FROM fingers WHERE domain = ? AND subject = ? ORDER BY id",
+        )
+        .bind(domain)
+        .bind(subject)
+        .fetch_all(&self.pool)
+        .await?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+        
+        let mut finger = WebFinger {
+            subject: subject.to_string(),
+            aliases: Vec::new(),
+            links: Vec::new(),
+            properties: IndexMap::new(),
+            redirect_to: None,
+        };
+        for row in rows {
+            if let Some(rel) = row.try_get::<Option<String>, _>("rel")? {
+                finger.links.push(Link {
+                    rel,
+                    href: row.try_get("href")?,
+                    template: None,
+                    titles: IndexMap::new(),
+                    properties: IndexMap::new(),
+                });
+            }
+            // A NULL value is kept as a null property
+            if let Some(key) = row.try_get::<Option<String>, _>("property_key")? {
+                finger.properties.insert(key, row.try_get("property_value")?);
+            }
+        }
+        Ok(Some(finger))
+    }
+    
+    async fn find(&self, domain: &str, resource: &str) -> Result<Lookup, sqlx::Error> {
+        if let Some(finger) = self.finger(domain, resource).await? {
+            return Ok(Ok(Found { tenant: TENANT.to_string(), outcome: Outcome::Hit, finger: Arc::new(finger) }));
+        }
+        
+        // Same wildcard semantics as a global tenant
+        let same_domain = extract_domain_from_resource(resource).is_some_and(|host| normalize_domain(host) == domain);
+        if same_domain {
+            if let Some(mut finger) = self.finger(domain, &format!("acct:*@{}", domain)).await? {
+                let user = extract_user_from_resource(resource).unwrap_or_default();
+                finger.subject = resource.to_string();
+                fill_placeholders(&mut finger.links, user, domain);
+                return Ok(Ok(Found { tenant: TENANT.to_string(), outcome: Outcome::GlobalHit, finger: Arc::new(finger) }));
+            }
+        }
+        
+        let known: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM fingers WHERE domain = ?)")
+            .bind(domain)
+            .fetch_one(&self.pool)
+            .await?;
+        let error = if known {
+            WebFingerError::ResourceNotFound { resource: resource.to_string(), domain: domain.to_string() }
+        } else {
+            WebFingerError::UnknownDomain { domain: domain.to_string() }
+        };
+        Ok(Err((TENANT.to_string(), error)))
+    }
+}
+
+impl FingerStore for SqliteStore {
+    fn lookup<'a>(&'a self, domain: &'a str, resource: &'a str) -> LookupFuture<'a> {
+        Box::pin(async move {
+            self.find(domain, resource).await.unwrap_or_else(|e| {
+                error!("SQLite lookup failed: {}", e);
+                Err((TENANT.to_string(), WebFingerError::StoreUnavailable))
+            })
+        })
+    }
+    
+    fn ready(&self) -> Result<(), String> {
+        if self.pool.is_closed() {
+            return Err("SQLite connection pool is closed".to_string());
+        }
+        Ok(())
+    }
+}
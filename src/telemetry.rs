@@ -0,0 +1,140 @@
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus_client::{
+    encoding::{text::encode, EncodeLabelSet},
+    metrics::{counter::Counter, exemplar::HistogramWithExemplars, family::Family, gauge::Gauge},
+    registry::{Registry, Unit},
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, LazyLock,
+    },
+    time::Instant,
+};
+
+use crate::{request_id, AppState};
+
+// Exposition format that carries exemplars
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+// Latency buckets in seconds, lookups are expected to take well under a millisecond
+const DURATION_BUCKETS: &[f64] = &[
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1,
+];
+
+// OpenMetrics caps an exemplar's labels at 128 characters, name included
+const MAX_EXEMPLAR_ID_LEN: usize = 100;
+
+// Tenant label used when the request domain matches no tenant
+pub const UNKNOWN_TENANT: &str = "unknown";
+
+#[derive(Clone, Copy)]
+pub enum Outcome {
+    Hit,
+    GlobalHit,
+    Miss,
+    BadRequest,
+    Redirect,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Hit => "hit",
+            Outcome::GlobalHit => "global_hit",
+            Outcome::Miss => "miss",
+            Outcome::BadRequest => "bad_request",
+            Outcome::Redirect => "redirect",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RequestLabels {
+    tenant: String,
+    outcome: &'static str,
+}
+
+// Links a latency observation to the request's X-Request-Id
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TraceLabels {
+    trace_id: String,
+}
+
+type DurationHistogram = HistogramWithExemplars<TraceLabels>;
+
+struct Metrics {
+    registry: Registry,
+    requests: Family<RequestLabels, Counter>,
+    duration: Family<RequestLabels, DurationHistogram, fn() -> DurationHistogram>,
+    in_flight: Gauge,
+}
+
+// Process-wide, like the handlers that record into it
+static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    let mut registry = Registry::default();
+    
+    let requests = Family::<RequestLabels, Counter>::default();
+    registry.register("webfinger_requests", "WebFinger requests by tenant and outcome", requests.clone());
+    
+    let duration: Family<RequestLabels, DurationHistogram, fn() -> DurationHistogram> =
+        Family::new_with_constructor(|| HistogramWithExemplars::new(DURATION_BUCKETS.iter().copied()));
+    registry.register_with_unit(
+        "webfinger_request_duration",
+        "WebFinger request latency",
+        Unit::Seconds,
+        duration.clone(),
+    );
+    
+    let in_flight = Gauge::default();
+    registry.register("http_requests_in_flight", "HTTP requests currently being handled", in_flight.clone());
+    
+    Metrics { registry, requests, duration, in_flight }
+});
+
+pub fn record_webfinger(tenant: &str, outcome: Outcome, started: Instant) {
+    let labels = RequestLabels { tenant: tenant.to_string(), outcome: outcome.as_str() };
+    let exemplar = request_id::current()
+        .filter(|id| id.len() <= MAX_EXEMPLAR_ID_LEN)
+        .map(|trace_id| TraceLabels { trace_id });
+    
+    METRICS.requests.get_or_create(&labels).inc();
+    METRICS.duration.get_or_create(&labels).observe(started.elapsed().as_secs_f64(), exemplar);
+}
+
+// Counts requests being handled, so a draining shutdown can be watched and reported
+pub async fn track_in_flight(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let _guard = InFlight::enter(state.in_flight.clone());
+    next.run(request).await
+}
+
+// Decrements on drop, so requests cancelled mid-flight are counted out too
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn enter(count: Arc<AtomicUsize>) -> Self {
+        let current = count.fetch_add(1, Ordering::Relaxed) + 1;
+        METRICS.in_flight.set(current as i64);
+        InFlight(count)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let current = self.0.fetch_sub(1, Ordering::Relaxed) - 1;
+        METRICS.in_flight.set(current as i64);
+    }
+}
+
+pub async fn metrics_handler() -> impl IntoResponse {
+    let mut body = String::new();
+    if encode(&mut body, &METRICS.registry).is_err() {
+        return axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    ([(header::CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)], body).into_response()
+}
@@ -0,0 +1,1670 @@
+use axum::{
+    body::{self, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use proptest::prelude::*;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+use crate::config::{normalize_acct, normalize_domain, Config, URNAliases, WebFinger};
+use crate::error::WebFingerError;
+use crate::settings::Settings;
+use crate::store::{FingerStore, Found, LookupFuture};
+use crate::telemetry::Outcome;
+use crate::{
+    extract_domain_from_resource, extract_user_from_resource, normalize_resource, router, strip_plus_tag, AppState,
+};
+
+const CONFIG: &str = r#"
+mysite:
+    domain: "mysite.com"
+    users:
+        alice@mysite.com:
+            "http://webfinger.net/rel/profile-page": "https://mysite.com/alice"
+            "http://schema.org/name": "Alice"
+
+everyone:
+    domain: "everyone.example"
+    global: true
+    global_links:
+        "http://webfinger.net/rel/profile-page": "https://everyone.example/@{user}"
+"#;
+
+fn app() -> Router {
+    app_with(CONFIG)
+}
+
+fn app_with(yaml: &str) -> Router {
+    app_with_settings(yaml, Settings::default())
+}
+
+fn app_with_settings(yaml: &str, settings: Settings) -> Router {
+    let config = Config::from_yaml(yaml, URNAliases::new()).expect("test config is valid");
+    router(AppState::new(config, settings))
+}
+
+async fn webfinger(app: Router, host: &str, query: &str) -> (StatusCode, Value) {
+    let request = Request::get(format!("/.well-known/webfinger?resource={}", query))
+        .header(header::HOST, host)
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    
+    let status = response.status();
+    let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn exact_match() {
+    let (status, jrd) = webfinger(app(), "mysite.com", "acct:alice@mysite.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:alice@mysite.com");
+    assert_eq!(jrd["links"][0]["rel"], "http://webfinger.net/rel/profile-page");
+    assert_eq!(jrd["links"][0]["href"], "https://mysite.com/alice");
+    assert_eq!(jrd["properties"]["http://schema.org/name"], "Alice");
+}
+
+#[tokio::test]
+async fn global_wildcard_is_personalized() {
+    let (status, jrd) = webfinger(app(), "everyone.example", "acct:bob@everyone.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:bob@everyone.example");
+    assert_eq!(jrd["links"][0]["href"], "https://everyone.example/@bob");
+}
+
+#[tokio::test]
+async fn unknown_domain() {
+    let (status, body) = webfinger(app(), "other.example", "acct:alice@other.example").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "unknown_domain");
+    assert_eq!(body["domain"], "other.example");
+}
+
+#[tokio::test]
+async fn unknown_resource() {
+    let (status, body) = webfinger(app(), "mysite.com", "acct:nobody@mysite.com").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "resource_not_found");
+    assert_eq!(body["resource"], "acct:nobody@mysite.com");
+}
+
+#[tokio::test]
+async fn host_port_is_ignored() {
+    let (status, jrd) = webfinger(app(), "mysite.com:8080", "acct:alice@mysite.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:alice@mysite.com");
+}
+
+fn rels(jrd: &Value) -> Vec<&str> {
+    let mut rels: Vec<&str> = jrd["links"]
+        .as_array()
+        .map(|links| links.iter().filter_map(|link| link["rel"].as_str()).collect())
+        .unwrap_or_default();
+    rels.sort();
+    rels
+}
+
+const LINKS_CONFIG: &str = r#"
+mysite:
+    domain: "mysite.com"
+    users:
+        alice@mysite.com:
+            "http://webfinger.net/rel/profile-page": "https://mysite.com/alice"
+            "http://webfinger.net/rel/avatar": "https://mysite.com/alice.png"
+            "http://openid.net/specs/connect/1.0/issuer": "https://sso.mysite.com"
+"#;
+
+#[tokio::test]
+async fn rel_exclusion() {
+    let query = "acct:alice@mysite.com&rel=-http://openid.net/specs/connect/1.0/issuer";
+    let (status, jrd) = webfinger(app_with(LINKS_CONFIG), "mysite.com", query).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rels(&jrd), ["http://webfinger.net/rel/avatar", "http://webfinger.net/rel/profile-page"]);
+}
+
+#[tokio::test]
+async fn rel_exclusion_wins_over_inclusion() {
+    let query = concat!(
+        "acct:alice@mysite.com",
+        "&rel=http://webfinger.net/rel/avatar",
+        "&rel=http://webfinger.net/rel/profile-page",
+        "&rel=-http://webfinger.net/rel/avatar",
+    );
+    let (status, jrd) = webfinger(app_with(LINKS_CONFIG), "mysite.com", query).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rels(&jrd), ["http://webfinger.net/rel/profile-page"]);
+}
+
+const DEFAULT_CONFIG: &str = r#"
+mysite:
+    domain: "mysite.com"
+    users:
+        alice@mysite.com:
+            "http://webfinger.net/rel/profile-page": "https://mysite.com/alice"
+
+fallback:
+    domain: "fallback.example"
+    global: true
+    default: true
+    global_links:
+        "http://webfinger.net/rel/profile-page": "https://{domain}/@{user}"
+"#;
+
+#[tokio::test]
+async fn default_tenant_serves_unmatched_domains() {
+    let (status, jrd) = webfinger(app_with(DEFAULT_CONFIG), "other.example", "acct:bob@other.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:bob@other.example");
+    assert_eq!(jrd["links"][0]["href"], "https://other.example/@bob");
+}
+
+#[tokio::test]
+async fn exact_domain_wins_over_default_tenant() {
+    let (status, body) = webfinger(app_with(DEFAULT_CONFIG), "mysite.com", "acct:bob@mysite.com").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "resource_not_found");
+}
+
+#[tokio::test]
+async fn default_tenant_redirects_only_its_own_domains() {
+    let yaml = DEFAULT_CONFIG.replace(
+        "    domain: \"fallback.example\"\n",
+        "    domains: [\"fallback.example\", \"old-fallback.example\"]\n    canonical_domain: \"fallback.example\"\n",
+    );
+    let (status, jrd) = webfinger(app_with(&yaml), "other.example", "acct:bob@other.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["links"][0]["href"], "https://other.example/@bob");
+    
+    let request = Request::get("/.well-known/webfinger?resource=acct:bob@old-fallback.example")
+        .header(header::HOST, "old-fallback.example")
+        .body(Body::empty())
+        .unwrap();
+    let response = app_with(&yaml).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "https://fallback.example/.well-known/webfinger?resource=acct:bob@old-fallback.example"
+    );
+}
+
+#[test]
+fn only_one_default_tenant() {
+    let yaml = r#"
+a:
+    domain: "a.example"
+    default: true
+b:
+    domain: "b.example"
+    default: true
+"#;
+    let Err(err) = Config::from_yaml(yaml, URNAliases::new()) else {
+        panic!("two default tenants were accepted");
+    };
+    let err = err.to_string();
+    assert!(err.contains("Only one tenant may set 'default', found: a, b"), "{}", err);
+}
+
+#[tokio::test]
+async fn zero_tenants() {
+    let config = Config::from_yaml("{}", URNAliases::new()).unwrap();
+    assert!(config.check_not_empty(false).is_ok());
+    assert!(config.check_not_empty(true).is_err());
+    
+    let (status, body) = webfinger(app_with("{}"), "mysite.com", "acct:alice@mysite.com").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "unknown_domain");
+    
+    let request = Request::get("/readyz").body(Body::empty()).unwrap();
+    let response = app_with("{}").oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn large_responses_are_compressed() {
+    let app = app_with_settings(CONFIG, Settings { compression_min_size: 16, ..Settings::default() });
+    
+    let request = Request::get("/.well-known/webfinger?resource=acct:alice@mysite.com")
+        .header(header::HOST, "mysite.com")
+        .header(header::ACCEPT_ENCODING, "gzip")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/jrd+json");
+    assert!(response.headers()[header::ETAG].to_str().unwrap().starts_with("W/"));
+    
+    // Health checks stay below the threshold
+    let request = Request::get("/healthz").header(header::ACCEPT_ENCODING, "gzip").body(Body::empty()).unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+}
+
+#[tokio::test]
+async fn subject_override() {
+    let yaml = r#"
+mysite:
+    domain: "mysite.com"
+    users:
+        bob@mysite.com:
+            subject: "https://mysite.com/users/bob"
+            "http://webfinger.net/rel/profile-page": "https://mysite.com/bob"
+"#;
+    let (status, jrd) = webfinger(app_with(yaml), "mysite.com", "acct:bob@MySite.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "https://mysite.com/users/bob");
+}
+
+#[tokio::test]
+async fn cors_only_on_discovery_routes() {
+    let request = Request::get("/.well-known/webfinger?resource=acct:alice@mysite.com")
+        .header(header::HOST, "mysite.com")
+        .header(header::ORIGIN, "https://app.example")
+        .body(Body::empty())
+        .unwrap();
+    let response = app().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    
+    let request = Request::get("/healthz").header(header::ORIGIN, "https://app.example").body(Body::empty()).unwrap();
+    let response = app().oneshot(request).await.unwrap();
+    assert!(response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+}
+
+#[tokio::test]
+async fn plus_tags_are_stripped_when_enabled() {
+    let yaml = r#"
+mysite:
+    domain: "mysite.com"
+    strip_plus_tags: true
+    users:
+        bob@mysite.com:
+            "http://webfinger.net/rel/profile-page": "https://mysite.com/bob"
+"#;
+    let (status, jrd) = webfinger(app_with(yaml), "mysite.com", "acct:bob%2Bfedi@mysite.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:bob+fedi@mysite.com");
+    assert_eq!(jrd["links"][0]["href"], "https://mysite.com/bob");
+    
+    let (status, _) = webfinger(app(), "mysite.com", "acct:alice%2Bfedi@mysite.com").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn post_is_method_not_allowed() {
+    let request = Request::post("/.well-known/webfinger?resource=acct:alice@mysite.com")
+        .header(header::HOST, "mysite.com")
+        .body(Body::empty())
+        .unwrap();
+    let response = app().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers()[header::ALLOW], "GET, HEAD");
+    
+    let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"], "method_not_allowed");
+}
+
+#[test]
+fn schema_describes_tenant_files() {
+    let schema: Value = serde_json::from_str(&crate::config::schema()).unwrap();
+    let tenant = &schema["definitions"]["TenantConfig"]["properties"];
+    assert!(tenant["users"].is_object());
+    assert!(tenant["link_templates"].is_object());
+    assert!(schema["definitions"]["LinkConfig"]["properties"]["href"].is_object());
+}
+
+#[tokio::test]
+async fn also_known_as() {
+    let yaml = r#"
+new:
+    domain: "new.example"
+    users:
+        bob@new.example:
+            also_known_as: ["acct:bob@old.example"]
+"#;
+    let (status, jrd) = webfinger(app_with(yaml), "new.example", "acct:bob@new.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["aliases"][0], "acct:bob@old.example");
+    assert_eq!(jrd["links"][0]["rel"], "https://www.w3.org/ns/activitystreams#alsoKnownAs");
+    assert_eq!(jrd["links"][0]["href"], "acct:bob@old.example");
+}
+
+#[tokio::test]
+async fn over_length_resource() {
+    let app = app_with_settings(CONFIG, Settings { max_resource_len: 64, ..Settings::default() });
+    
+    let resource = format!("acct:{}@mysite.com", "a".repeat(64));
+    let (status, body) = webfinger(app.clone(), "mysite.com", &resource).await;
+    assert_eq!(status, StatusCode::URI_TOO_LONG);
+    assert_eq!(body["error"], "resource_too_long");
+    
+    let (status, _) = webfinger(app, "mysite.com", "acct:alice@mysite.com").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[test]
+fn dump_lists_processed_fingers() {
+    let config = Config::from_yaml(CONFIG, URNAliases::new()).unwrap();
+    let dump: Value = serde_json::from_str(&config.dump().unwrap()).unwrap();
+    let alice = &dump["mysite"]["fingers"]["acct:alice@mysite.com"];
+    assert_eq!(alice["links"][0]["href"], "https://mysite.com/alice");
+    assert_eq!(dump["everyone"]["global"], true);
+}
+
+#[tokio::test]
+async fn tenant_language() {
+    let yaml = r#"
+mysite:
+    domain: "mysite.com"
+    language: "en-US"
+    users:
+        alice@mysite.com:
+            "http://webfinger.net/rel/profile-page":
+                href: "https://mysite.com/alice"
+                title: "Alice's profile"
+"#;
+    let request = Request::get("/.well-known/webfinger?resource=acct:alice@mysite.com")
+        .header(header::HOST, "mysite.com")
+        .body(Body::empty())
+        .unwrap();
+    let response = app_with(yaml).oneshot(request).await.unwrap();
+    assert_eq!(response.headers()[header::CONTENT_LANGUAGE], "en-US");
+    
+    let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let jrd: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(jrd["links"][0]["titles"]["en-US"], "Alice's profile");
+}
+
+proptest! {
+    // Whatever a client sends, resource handling must not panic
+    #[test]
+    fn resource_parsing_never_panics(resource in any::<String>()) {
+        let normalized = normalize_resource(&resource);
+        for resource in [resource.as_str(), normalized.as_str()] {
+            let _ = extract_user_from_resource(resource);
+            let _ = extract_domain_from_resource(resource);
+            let _ = strip_plus_tag(resource);
+            let _ = normalize_acct(resource);
+        }
+    }
+    
+    // The lookup key and the global-match path must agree on the host
+    #[test]
+    fn domain_extraction_is_consistent(user in any::<String>(), domain in "[^@]*") {
+        let resource = format!("acct:{}@{}", user, domain);
+        prop_assert_eq!(extract_user_from_resource(&resource), Some(user.as_str()));
+        prop_assert_eq!(extract_domain_from_resource(&resource), Some(domain.as_str()));
+        
+        let normalized = normalize_acct(&resource);
+        let host = normalize_domain(&domain);
+        prop_assert_eq!(extract_domain_from_resource(&normalized), Some(host.as_str()));
+    }
+}
+
+#[tokio::test]
+async fn version_reports_build() {
+    let request = Request::get("/version").body(Body::empty()).unwrap();
+    let response = app().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    
+    let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let version: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+    assert!(version["commit"].is_string());
+    assert!(version["uptime_secs"].is_u64());
+}
+
+#[tokio::test]
+async fn rel_filter_resolves_urn_aliases() {
+    let yaml = r#"
+mysite:
+    domain: "mysite.com"
+    urn_aliases:
+        profile: "http://webfinger.net/rel/profile-page"
+        avatar: "http://webfinger.net/rel/avatar"
+    users:
+        alice@mysite.com:
+            profile: "https://mysite.com/alice"
+            avatar: "https://mysite.com/alice.png"
+"#;
+    let (status, jrd) = webfinger(app_with(yaml), "mysite.com", "acct:alice@mysite.com&rel=profile").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rels(&jrd), ["http://webfinger.net/rel/profile-page"]);
+    
+    let (_, jrd) = webfinger(app_with(yaml), "mysite.com", "acct:alice@mysite.com&rel=-profile").await;
+    assert_eq!(rels(&jrd), ["http://webfinger.net/rel/avatar"]);
+}
+
+#[tokio::test]
+async fn latency_exemplar_carries_request_id() {
+    // A tenant of its own, so parallel tests can't replace the exemplar
+    let yaml = r#"
+exemplar:
+    domain: "exemplar.example"
+    users:
+        alice@exemplar.example:
+            "http://webfinger.net/rel/profile-page": "https://exemplar.example/alice"
+"#;
+    let app = app_with(yaml);
+    let request = Request::get("/.well-known/webfinger?resource=acct:alice@exemplar.example")
+        .header(header::HOST, "exemplar.example")
+        .header("x-request-id", "exemplar-test-id")
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap();
+    
+    let response = app.oneshot(Request::get("/metrics").body(Body::empty()).unwrap()).await.unwrap();
+    let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains(r#"tenant="exemplar",outcome="hit"} 1 # {trace_id="exemplar-test-id"}"#), "{}", body);
+}
+
+#[tokio::test]
+async fn global_tenant_without_confirming_unknown_users() {
+    let yaml = r#"
+everyone:
+    domain: "everyone.example"
+    global: true
+    confirm_unknown_users: false
+    openid: "https://sso.everyone.example"
+    users:
+        alice@everyone.example:
+            "http://webfinger.net/rel/profile-page": "https://everyone.example/alice"
+"#;
+    let (status, _) = webfinger(app_with(yaml), "everyone.example", "acct:bob@everyone.example").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    
+    let (status, jrd) = webfinger(app_with(yaml), "everyone.example", "acct:alice@everyone.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(rels(&jrd).contains(&"http://openid.net/specs/connect/1.0/issuer"));
+}
+
+// Answers every resource with an empty finger, like a backend the config doesn't know about
+struct EchoStore;
+
+impl FingerStore for EchoStore {
+    fn ready(&self) -> Result<(), String> {
+        Ok(())
+    }
+    
+    fn lookup<'a>(&'a self, _domain: &'a str, resource: &'a str) -> LookupFuture<'a> {
+        let finger = WebFinger {
+            subject: resource.to_string(),
+            aliases: Vec::new(),
+            links: Vec::new(),
+            properties: Default::default(),
+            redirect_to: None,
+        };
+        Box::pin(async move { Ok(Found { tenant: "echo".to_string(), outcome: Outcome::Hit, finger: Arc::new(finger) }) })
+    }
+}
+
+#[tokio::test]
+async fn lookups_go_through_the_store() {
+    let config = Config::from_yaml("{}", URNAliases::new()).unwrap();
+    let mut state = AppState::new(config, Settings::default());
+    state.store = Arc::new(EchoStore);
+    
+    let (status, jrd) = webfinger(router(state), "anywhere.example", "acct:bob@anywhere.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:bob@anywhere.example");
+}
+
+// Never answers in time, like a backend that hangs
+struct SlowStore;
+
+impl FingerStore for SlowStore {
+    fn ready(&self) -> Result<(), String> {
+        Ok(())
+    }
+    
+    fn lookup<'a>(&'a self, domain: &'a str, resource: &'a str) -> LookupFuture<'a> {
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            EchoStore.lookup(domain, resource).await
+        })
+    }
+}
+
+#[tokio::test]
+async fn slow_lookups_time_out() {
+    let config = Config::from_yaml("{}", URNAliases::new()).unwrap();
+    let settings = Settings { request_timeout: Duration::from_millis(50), ..Settings::default() };
+    let state = AppState { store: Arc::new(SlowStore), ..AppState::new(config, settings) };
+    let app = router(state);
+    
+    let request = Request::get("/.well-known/webfinger?resource=acct:bob@slow.example")
+        .header(header::HOST, "slow.example")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+    
+    let response = app
+        .oneshot(Request::get("/healthz").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+// Fails with StoreUnavailable while `failing` is set, counting every call
+#[derive(Default)]
+struct FlakyStore {
+    failing: AtomicBool,
+    calls: AtomicUsize,
+}
+
+impl FingerStore for Arc<FlakyStore> {
+    fn ready(&self) -> Result<(), String> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err("flaky backend is down".to_string());
+        }
+        Ok(())
+    }
+    
+    fn lookup<'a>(&'a self, domain: &'a str, resource: &'a str) -> LookupFuture<'a> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if self.failing.load(Ordering::SeqCst) {
+            return Box::pin(async { Err(("flaky".to_string(), WebFingerError::StoreUnavailable)) });
+        }
+        EchoStore.lookup(domain, resource)
+    }
+}
+
+#[tokio::test]
+async fn circuit_breaker_opens_and_probes() {
+    use crate::settings::BreakerSettings;
+    use crate::store::CircuitBreaker;
+    
+    let flaky = Arc::new(FlakyStore::default());
+    let settings = BreakerSettings { failures: 2, cooldown: Duration::from_millis(50) };
+    let breaker = CircuitBreaker::new(Arc::new(flaky.clone()), settings);
+    let lookup = || breaker.lookup("flaky.example", "acct:bob@flaky.example");
+    let calls = || flaky.calls.load(Ordering::SeqCst);
+    
+    flaky.failing.store(true, Ordering::SeqCst);
+    assert!(lookup().await.is_err());
+    assert!(lookup().await.is_err());
+    assert_eq!(calls(), 2);
+    // Open: answered without touching the store
+    assert!(matches!(lookup().await, Err((_, WebFingerError::StoreUnavailable))));
+    assert_eq!(calls(), 2);
+    
+    // A failed probe opens it again straight away
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(lookup().await.is_err());
+    assert!(lookup().await.is_err());
+    assert_eq!(calls(), 3);
+    
+    // A good probe closes it
+    flaky.failing.store(false, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(lookup().await.is_ok());
+    assert!(lookup().await.is_ok());
+    assert_eq!(calls(), 5);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn sqlite_store() {
+    use crate::store::SqliteStore;
+    
+    // Shared cache keeps the in-memory database alive across pool connections
+    let url = "sqlite:file:sqlite_store_test?mode=memory&cache=shared";
+    let store = SqliteStore::connect(url).await.unwrap();
+    let pool = sqlx::SqlitePool::connect(url).await.unwrap();
+    sqlx::query(
+        "INSERT INTO fingers (domain, subject, rel, href, property_key, property_value) VALUES
+            ('db.example', 'acct:alice@db.example', 'http://webfinger.net/rel/profile-page', 'https://db.example/alice', NULL, NULL),
+            ('db.example', 'acct:alice@db.example', NULL, NULL, 'http://schema.org/name', 'Alice'),
+            ('db.example', 'acct:*@db.example', 'http://webfinger.net/rel/profile-page', 'https://db.example/@{user}', NULL, NULL)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    
+    let config = Config::from_yaml("{}", URNAliases::new()).unwrap();
+    let state = AppState { store: Arc::new(store), ..AppState::new(config, Settings::default()) };
+    let app = router(state);
+    
+    let (status, jrd) = webfinger(app.clone(), "db.example", "acct:alice@db.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["links"][0]["href"], "https://db.example/alice");
+    assert_eq!(jrd["properties"]["http://schema.org/name"], "Alice");
+    
+    let (status, jrd) = webfinger(app.clone(), "db.example", "acct:bob@db.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:bob@db.example");
+    assert_eq!(jrd["links"][0]["href"], "https://db.example/@bob");
+    
+    let (status, body) = webfinger(app, "other.example", "acct:bob@other.example").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "unknown_domain");
+}
+
+#[tokio::test]
+async fn signed_responses_verify() {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use ed25519_dalek::{pkcs8::{spki::der::pem::LineEnding, EncodePrivateKey}, Signature, SigningKey, Verifier};
+    use sha2::{Digest, Sha256};
+    
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let path = std::env::temp_dir().join(format!("rustfinger-signing-{}.pem", std::process::id()));
+    std::fs::write(&path, key.to_pkcs8_pem(LineEnding::LF).unwrap().as_bytes()).unwrap();
+    let signed = app_with(&format!(
+        r#"
+signed:
+    domain: "signed.example"
+    signing_key: "{}"
+    users:
+        alice@signed.example:
+            "http://webfinger.net/rel/profile-page": "https://signed.example/alice"
+"#,
+        path.display()
+    ));
+    std::fs::remove_file(&path).unwrap();
+    
+    let request = Request::get("/.well-known/webfinger?resource=acct:alice@signed.example")
+        .header(header::HOST, "signed.example")
+        .body(Body::empty())
+        .unwrap();
+    let response = signed.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers().clone();
+    let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    
+    let digest = headers["content-digest"].to_str().unwrap();
+    assert_eq!(digest, format!("sha-256=:{}:", STANDARD.encode(Sha256::digest(&body))));
+    let params = headers["signature-input"].to_str().unwrap().strip_prefix("sig1=").unwrap();
+    assert!(params.starts_with(r#"("@status" "content-type" "content-digest");created="#));
+    assert!(params.ends_with(r#";keyid="signed";alg="ed25519""#));
+    
+    // Rebuild the signature base as a verifier would
+    let base = format!(
+        "\"@status\": 200\n\"content-type\": {}\n\"content-digest\": {}\n\"@signature-params\": {}",
+        headers[header::CONTENT_TYPE].to_str().unwrap(),
+        digest,
+        params
+    );
+    let signature = headers["signature"].to_str().unwrap();
+    let signature = signature.strip_prefix("sig1=:").and_then(|s| s.strip_suffix(':')).unwrap();
+    let signature = Signature::from_slice(&STANDARD.decode(signature).unwrap()).unwrap();
+    key.verifying_key().verify(base.as_bytes(), &signature).unwrap();
+    
+    // Tenants without a key stay unsigned
+    let request = Request::get("/.well-known/webfinger?resource=acct:alice@mysite.com")
+        .header(header::HOST, "mysite.com")
+        .body(Body::empty())
+        .unwrap();
+    let response = app().oneshot(request).await.unwrap();
+    assert!(!response.headers().contains_key("signature"));
+}
+
+#[tokio::test]
+async fn minimal_responses() {
+    let app = app_with(
+        r#"
+minimal:
+    domain: "minimal.example"
+    users:
+        bob@minimal.example:
+            aliases: ["https://minimal.example/@bob"]
+            "http://webfinger.net/rel/profile-page": "https://minimal.example/@bob"
+            "http://webfinger.net/rel/avatar": "https://minimal.example/bob.png"
+            "http://schema.org/name": "Bob"
+"#,
+    );
+    
+    let (status, jrd) = webfinger(app.clone(), "minimal.example", "acct:bob@minimal.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(jrd.get("aliases").is_some() && jrd.get("properties").is_some());
+    
+    let (status, jrd) = webfinger(app, "minimal.example", "acct:bob@minimal.example&rel=http://webfinger.net/rel/profile-page&minimal=1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        jrd,
+        serde_json::json!({
+            "subject": "acct:bob@minimal.example",
+            "links": [{ "rel": "http://webfinger.net/rel/profile-page", "href": "https://minimal.example/@bob" }],
+        })
+    );
+}
+
+#[tokio::test]
+async fn misspelled_well_known_paths_redirect() {
+    let get = |app: Router, path: &'static str| async move {
+        app.oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap()
+    };
+    
+    let response = get(app(), "/.well-known/WebFinger/?resource=acct:alice@mysite.com").await;
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(response.headers()[header::LOCATION], "/.well-known/webfinger?resource=acct:alice@mysite.com");
+    let response = get(app(), "/.well-known/host-meta.JSON").await;
+    assert_eq!(response.headers()[header::LOCATION], "/.well-known/host-meta.json");
+    
+    // Only the discovery paths are tolerated
+    assert_eq!(get(app(), "/healthz/").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(get(app(), "/.well-known/nodeinfo").await.status(), StatusCode::NOT_FOUND);
+    
+    let strict = app_with_settings(CONFIG, Settings { strict_paths: true, ..Settings::default() });
+    assert_eq!(get(strict, "/.well-known/webfinger/").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn config_hash_follows_reloads() {
+    let hash_of = |app: Router| async move {
+        let response = app.oneshot(Request::get("/readyz").body(Body::empty()).unwrap()).await.unwrap();
+        let hash = response.headers()["x-config-hash"].to_str().unwrap().to_string();
+        let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).ends_with(&format!("config {}", hash)));
+        hash
+    };
+    
+    // Stable across loads of the same files, whatever the map order
+    let state = AppState::new(Config::from_yaml(CONFIG, URNAliases::new()).unwrap(), Settings::default());
+    let before = hash_of(router(state.clone())).await;
+    assert_eq!(before.len(), 64);
+    assert_eq!(before, hash_of(app()).await);
+    
+    let changed = CONFIG.replace("https://mysite.com/alice", "https://mysite.com/~alice");
+    state.config.store(Arc::new(Config::from_yaml(&changed, URNAliases::new()).unwrap()));
+    assert_ne!(before, hash_of(router(state)).await);
+}
+
+#[tokio::test]
+async fn aggregator_hosts_match_by_resource_domain() {
+    let settings = Settings { aggregator_hosts: vec!["finger.example".to_string()], ..Settings::default() };
+    let app = app_with_settings(CONFIG, settings);
+    
+    let (status, jrd) = webfinger(app.clone(), "finger.example", "acct:alice@mysite.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["links"][0]["href"], "https://mysite.com/alice");
+    let (status, jrd) = webfinger(app.clone(), "finger.example", "acct:bob@everyone.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:bob@everyone.example");
+    
+    // Other hosts still match by Host
+    let (status, _) = webfinger(app, "mysite.com", "acct:bob@everyone.example").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = webfinger(app_with(CONFIG), "finger.example", "acct:alice@mysite.com").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn server_header_is_configurable() {
+    let server = |app: Router, path: &'static str| async move {
+        let response = app.oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap();
+        response.headers().get(header::SERVER).map(|value| value.to_str().unwrap().to_string())
+    };
+    
+    let settings = Settings { server_header: Some(header::HeaderValue::from_static("finger")), ..Settings::default() };
+    let named = app_with_settings(CONFIG, settings);
+    assert_eq!(server(named.clone(), "/healthz").await.as_deref(), Some("finger"));
+    assert_eq!(server(named.clone(), "/.well-known/webfinger?resource=acct:nobody@mysite.com").await.as_deref(), Some("finger"));
+    assert_eq!(server(named, "/nowhere").await.as_deref(), Some("finger"));
+    
+    assert_eq!(server(app(), "/healthz").await, None);
+}
+
+#[tokio::test]
+async fn urn_alias_files_merge_over_the_base() {
+    use crate::config::merge_urn_alias_files;
+    
+    let dir = std::env::temp_dir().join(format!("rustfinger-urns-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.urns.yml"), "blog: \"https://example.com/rel/blog\"\nname: \"https://example.com/rel/name\"\n").unwrap();
+    std::fs::write(dir.join("b.urns.yml"), "blog: \"https://example.com/rel/blog\"\nchat: \"https://example.com/rel/chat\"\n").unwrap();
+    std::fs::write(dir.join("tenant.yml"), "name: \"not an alias file\"\n").unwrap();
+    
+    let base = URNAliases::from([
+        ("name".to_string(), "http://schema.org/name".to_string()),
+        ("avatar".to_string(), "http://webfinger.net/rel/avatar".to_string()),
+    ]);
+    let aliases = merge_urn_alias_files(&dir, base.clone()).await.unwrap();
+    assert_eq!(aliases.len(), 4);
+    assert_eq!(aliases["name"], "https://example.com/rel/name");
+    assert_eq!(aliases["avatar"], "http://webfinger.net/rel/avatar");
+    assert_eq!(aliases["chat"], "https://example.com/rel/chat");
+    
+    std::fs::write(dir.join("c.urns.yml"), "chat: \"https://other.example/rel/chat\"\n").unwrap();
+    let err = merge_urn_alias_files(&dir, base).await.unwrap_err().to_string();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(err.contains("'chat'") && err.contains("b.urns.yml") && err.contains("c.urns.yml"), "{}", err);
+}
+
+#[test]
+fn lint_reports_unused_aliases_and_unknown_keys() {
+    let aliases = URNAliases::from([
+        ("avatar".to_string(), "http://webfinger.net/rel/avatar".to_string()),
+        ("name".to_string(), "http://schema.org/name".to_string()),
+        ("openid".to_string(), "http://openid.net/specs/connect/1.0/issuer".to_string()),
+    ]);
+    let findings = Config::lint_yaml(
+        r#"
+linted:
+    domain: "linted.example"
+    openid: "https://linted.example/auth"
+    urn_aliases:
+        blog: "https://linted.example/rel/blog"
+    users:
+        bob@linted.example:
+            avatar: "https://linted.example/bob.png"
+            "http://schema.org/name": "Bob"
+            "http://webfinger.net/rel/profile-pgae": "https://linted.example/@bob"
+"#,
+        &aliases,
+    )
+    .unwrap();
+    assert_eq!(
+        findings,
+        [
+            "URN alias 'name' is never used",
+            "tenant 'linted' (test.yml), user 'bob@linted.example': 'http://webfinger.net/rel/profile-pgae' is not an alias, and no alias maps to it",
+            "tenant 'linted' (test.yml): urn_alias 'blog' is never used",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn missing_host_is_a_bad_request() {
+    let lookup = |app: Router| async move {
+        let request = Request::get("/.well-known/webfinger?resource=acct:alice@localhost").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice::<Value>(&body).unwrap())
+    };
+    
+    let (status, body) = lookup(app()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "missing_host");
+    
+    let (status, _) = webfinger(app(), "", "acct:alice@mysite.com").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    
+    let local = r#"
+local:
+    domain: "localhost"
+    global: true
+    global_links:
+        "http://webfinger.net/rel/profile-page": "http://localhost/{user}"
+"#;
+    let (status, jrd) = lookup(app_with_settings(local, Settings { allow_missing_host: true, ..Settings::default() })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:alice@localhost");
+}
+
+#[tokio::test]
+async fn wildcard_subdomains_pick_the_most_specific_pattern() {
+    let app = app_with(
+        r#"
+teams:
+    domain: "teams.example"
+    global: true
+    global_links:
+        "http://webfinger.net/rel/profile-page": "https://teams.example/{user}"
+    wildcard_subdomains:
+        "*.teams.example":
+            "http://webfinger.net/rel/profile-page": "https://{subdomain}.teams.example/~{user}"
+        "staff.teams.example":
+            "http://webfinger.net/rel/profile-page": "https://teams.example/staff/{user}@{domain}"
+"#,
+    );
+    let profile = |app: Router, resource: &'static str| async move {
+        let (status, jrd) = webfinger(app, "teams.example", resource).await;
+        assert_eq!(status, StatusCode::OK, "{}", resource);
+        assert_eq!(jrd["subject"], resource);
+        jrd["links"][0]["href"].as_str().unwrap().to_string()
+    };
+    
+    assert_eq!(profile(app.clone(), "acct:bob@teams.example").await, "https://teams.example/bob");
+    assert_eq!(profile(app.clone(), "acct:bob@red.teams.example").await, "https://red.teams.example/~bob");
+    assert_eq!(profile(app.clone(), "acct:bob@STAFF.teams.example").await, "https://teams.example/staff/bob@staff.teams.example");
+    
+    // Subdomains of other hosts are not ours to answer for
+    let (status, _) = webfinger(app, "teams.example", "acct:bob@red.other.example").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    
+    let Err(err) = Config::from_yaml(
+        r#"
+teams:
+    domain: "teams.example"
+    global: true
+    wildcard_subdomains:
+        "*.other.example": {}
+"#,
+        URNAliases::new(),
+    ) else {
+        panic!("pattern outside the tenant's domains was accepted");
+    };
+    assert!(err.to_string().contains("'*.other.example' is not a subdomain"), "{}", err);
+}
+
+#[test]
+fn access_log_lines() {
+    use crate::access_log::{AccessLogFormat, Entry};
+    use axum::http::{Method, Uri, Version};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    
+    let uri: Uri = "/.well-known/webfinger?resource=acct:bob@example.com&rel=self".parse().unwrap();
+    let entry = Entry {
+        ip: Some("192.0.2.7".parse().unwrap()),
+        method: &Method::GET,
+        uri: &uri,
+        version: Version::HTTP_11,
+        status: StatusCode::OK,
+        bytes: Some(512),
+        referer: None,
+        user_agent: Some("Mastodon/4.2 (\"bot\")"),
+        time: UNIX_EPOCH + Duration::from_secs(1_000_000_000),
+    };
+    assert_eq!(
+        entry.line(AccessLogFormat::Clf, false),
+        r#"192.0.2.7 - - [09/Sep/2001:01:46:40 +0000] "GET /.well-known/webfinger?resource=acct:bob@example.com&rel=self HTTP/1.1" 200 512"#
+    );
+    assert_eq!(
+        entry.line(AccessLogFormat::Combined, true),
+        r#"192.0.2.7 - - [09/Sep/2001:01:46:40 +0000] "GET /.well-known/webfinger?resource=redacted&rel=self HTTP/1.1" 200 512 "-" "Mastodon/4.2 (\"bot\")""#
+    );
+    
+    let leap_day = Entry { ip: None, bytes: None, time: SystemTime::UNIX_EPOCH + Duration::from_secs(951_782_400), ..entry };
+    assert!(leap_day.line(AccessLogFormat::Clf, false).starts_with("- - - [29/Feb/2000:00:00:00 +0000]"));
+    assert!(leap_day.line(AccessLogFormat::Clf, false).ends_with(" 200 -"));
+}
+
+#[tokio::test]
+async fn json_content_type_follows_accept() {
+    let content_type = |accept: &'static str| async move {
+        let request = Request::get("/.well-known/webfinger?resource=acct:alice@mysite.com")
+            .header(header::HOST, "mysite.com")
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response.headers()[header::CONTENT_TYPE].to_str().unwrap().to_string()
+    };
+    
+    assert_eq!(content_type("application/json").await, "application/json");
+    assert_eq!(content_type("application/jrd+json;q=0.5, application/json").await, "application/json");
+    // Either is fine: JRD wins
+    assert_eq!(content_type("application/json, application/jrd+json").await, "application/jrd+json");
+    assert_eq!(content_type("*/*").await, "application/jrd+json");
+    assert_eq!(content_type("application/xrd+xml;q=0.5, application/json").await, "application/json");
+}
+
+#[tokio::test]
+async fn links_keep_declaration_order() {
+    let yaml = r#"
+ordered:
+    domain: "ordered.example"
+    link_templates:
+        "https://ordered.example/rel/template": "https://ordered.example/{user}/template"
+    users:
+        bob@ordered.example:
+            "https://ordered.example/rel/c": "https://ordered.example/c"
+            "https://ordered.example/rel/a": "https://ordered.example/a"
+            "https://ordered.example/rel/e": "https://ordered.example/e"
+            "https://ordered.example/rel/b": "https://ordered.example/b"
+            "https://ordered.example/rel/d": "https://ordered.example/d"
+            links:
+                "https://ordered.example/rel/z": "https://ordered.example/z"
+                "https://ordered.example/rel/y": "https://ordered.example/y"
+"#;
+    let rels = |jrd: Value| -> Vec<String> {
+        let links = jrd["links"].as_array().unwrap().iter();
+        links.map(|link| link["rel"].as_str().unwrap().replace("https://ordered.example/rel/", "")).collect()
+    };
+    
+    let (_, first) = webfinger(app_with(yaml), "ordered.example", "acct:bob@ordered.example").await;
+    let (_, second) = webfinger(app_with(yaml), "ordered.example", "acct:bob@ordered.example").await;
+    assert_eq!(rels(first), ["c", "a", "e", "b", "d", "z", "y", "template"]);
+    assert_eq!(rels(second), ["c", "a", "e", "b", "d", "z", "y", "template"]);
+}
+
+#[tokio::test]
+async fn preflight_gets_no_content() {
+    let settings = Settings {
+        cors_allow_origins: vec![header::HeaderValue::from_static("https://app.example")],
+        cors_max_age: Duration::from_secs(600),
+        ..Settings::default()
+    };
+    let preflight = |app: Router, origin: &'static str| async move {
+        let request = Request::options("/.well-known/webfinger?resource=acct:alice@mysite.com")
+            .header(header::HOST, "mysite.com")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "x-requested-with")
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap()
+    };
+    
+    let response = preflight(app_with_settings(CONFIG, settings.clone()), "https://app.example").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let headers = response.headers();
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example");
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_METHODS], "GET,HEAD");
+    assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+    assert!(headers.contains_key(header::ACCESS_CONTROL_ALLOW_HEADERS));
+    let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert!(body.is_empty());
+    
+    // Other origins get no grant to read the response
+    let response = preflight(app_with_settings(CONFIG, settings), "https://evil.example").await;
+    assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+#[tokio::test]
+async fn null_properties_are_served_as_null() {
+    let yaml = r#"
+nulls:
+    domain: "nulls.example"
+    users:
+        carol@nulls.example:
+            "http://schema.org/name": ~
+            properties:
+                "http://schema.org/email":
+                "http://schema.org/nickname": "caz"
+"#;
+    let (status, jrd) = webfinger(app_with(yaml), "nulls.example", "acct:carol@nulls.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(jrd["properties"]["http://schema.org/name"].is_null());
+    assert!(jrd["properties"]["http://schema.org/email"].is_null());
+    assert_eq!(jrd["properties"].as_object().unwrap().len(), 3);
+    assert_eq!(jrd["properties"]["http://schema.org/nickname"], "caz");
+    
+    let broken = "nulls:\n    domain: \"nulls.example\"\n    users:\n        carol@nulls.example:\n            links:\n                \"http://webfinger.net/rel/profile-page\": ~\n";
+    assert!(Config::from_yaml(broken, URNAliases::new()).is_err());
+}
+
+#[test]
+fn resolve_works_without_a_server() {
+    let config = Config::from_yaml(CONFIG, URNAliases::new()).unwrap();
+    
+    let finger = crate::resolve(&config, "MySite.com", "ACCT:alice@mysite.com", &[]).unwrap();
+    assert_eq!(finger.subject, "acct:alice@mysite.com");
+    assert_eq!(finger.links.len(), 1);
+    
+    let rels = ["http://example.com/none".to_string()];
+    let finger = crate::resolve(&config, "everyone.example", "acct:bob@everyone.example", &rels).unwrap();
+    assert_eq!(finger.subject, "acct:bob@everyone.example");
+    assert!(finger.links.is_empty());
+    
+    assert!(crate::resolve(&config, "mysite.com", "acct:nobody@mysite.com", &[]).is_none());
+    assert!(crate::resolve(&config, "mysite.com", "not a uri", &[]).is_none());
+}
+
+#[test]
+fn watch_combines_only_with_dump_config() {
+    let parse = |args: &[&str]| crate::cli::Command::parse(args.iter().map(|arg| arg.to_string()));
+    assert!(matches!(parse(&["--watch"]), Ok(crate::cli::Command::Watch { dump: false })));
+    assert!(matches!(parse(&["--dump-config", "--watch"]), Ok(crate::cli::Command::Watch { dump: true })));
+    assert!(parse(&["--watch", "--lint"]).is_err());
+}
+
+#[tokio::test]
+async fn old_handles_redirect_to_the_canonical_subject() {
+    let yaml = r#"
+moved:
+    domain: "moved.example"
+    users:
+        oldcarol@moved.example:
+            redirect_to: "acct:carol@moved.example"
+        carol@moved.example:
+            "http://webfinger.net/rel/profile-page": "https://moved.example/carol"
+"#;
+    let request = Request::get("/.well-known/webfinger?resource=acct:oldcarol@moved.example&rel=self")
+        .header(header::HOST, "moved.example")
+        .body(Body::empty())
+        .unwrap();
+    let response = app_with(yaml).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "https://moved.example/.well-known/webfinger?resource=acct%3Acarol%40moved.example&rel=self"
+    );
+    
+    let (status, jrd) = webfinger(app_with(yaml), "moved.example", "acct:carol@moved.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(jrd.get("redirect_to").is_none());
+    
+    let looping = "moved:\n    domain: \"moved.example\"\n    users:\n        carol@moved.example:\n            redirect_to: \"acct:carol@moved.example\"\n";
+    assert!(Config::from_yaml(looping, URNAliases::new()).is_err());
+}
+
+#[tokio::test]
+async fn openid_rel_follows_the_alias_or_defaults_to_the_issuer_urn() {
+    let yaml = r#"
+issuer:
+    domain: "issuer.example"
+    openid: "https://sso.issuer.example"
+    users:
+        alice@issuer.example:
+            "http://webfinger.net/rel/profile-page": "https://issuer.example/alice"
+"#;
+    let (_, jrd) = webfinger(app_with(yaml), "issuer.example", "acct:alice@issuer.example").await;
+    assert!(rels(&jrd).contains(&"http://openid.net/specs/connect/1.0/issuer"));
+    assert!(!rels(&jrd).contains(&"openid"));
+    
+    let aliases = URNAliases::from([("openid".to_string(), "https://issuer.example/rel/sso".to_string())]);
+    let config = Config::from_yaml(yaml, aliases).unwrap();
+    let finger = crate::resolve(&config, "issuer.example", "acct:alice@issuer.example", &[]).unwrap();
+    assert!(finger.links.iter().any(|link| link.rel == "https://issuer.example/rel/sso"));
+}
+
+#[test]
+fn import_keeps_loadable_rows_and_reports_the_rest() {
+    let csv = "subject,rel,href\n\
+        alice@import.example,http://webfinger.net/rel/profile-page,https://import.example/alice\n\
+        bob@other.example,http://webfinger.net/rel/profile-page,https://other.example/bob\n\
+        carol@import.example,http://webfinger.net/rel/profile-page,not a url\n\
+        alice@import.example,http://webfinger.net/rel/profile-page,https://import.example/again\n\
+        dave@import.example,http://webfinger.net/rel/profile-page,https://import.example/${RUSTFINGER_UNSET}/dave\n";
+    let imported = crate::import::import(std::path::Path::new("people.csv"), csv, &URNAliases::new()).unwrap();
+    assert_eq!(imported.tenant, "people");
+    assert_eq!(imported.users, 3);
+    assert_eq!(imported.rejected.len(), 2);
+    assert!(imported.rejected[0].starts_with("line 4: "));
+    
+    let config = Config::from_yaml(&imported.yaml, URNAliases::new()).unwrap();
+    assert!(crate::resolve(&config, "other.example", "acct:bob@other.example", &[]).is_some());
+    assert!(crate::resolve(&config, "import.example", "acct:carol@import.example", &[]).is_none());
+    // Loading expands ${VAR}, the imported href has to survive it as written
+    let dave = crate::resolve(&config, "import.example", "acct:dave@import.example", &[]).unwrap();
+    assert_eq!(dave.links[0].href.as_deref(), Some("https://import.example/${RUSTFINGER_UNSET}/dave"));
+}
+
+#[tokio::test]
+async fn openid_issuers_are_ordered_by_priority() {
+    let yaml = r#"
+issuers:
+    domain: "issuers.example"
+    openid:
+        - href: "https://backup.issuers.example"
+        - href: "https://secondary.issuers.example"
+          priority: 20
+        - href: "https://primary.issuers.example"
+          priority: 10
+    users:
+        alice@issuers.example:
+            "http://webfinger.net/rel/profile-page": "https://issuers.example/alice"
+"#;
+    let (status, jrd) = webfinger(app_with(yaml), "issuers.example", "acct:alice@issuers.example").await;
+    assert_eq!(status, StatusCode::OK);
+    let issuers: Vec<&Value> = jrd["links"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|link| link["rel"] == "http://openid.net/specs/connect/1.0/issuer")
+        .collect();
+    let hrefs: Vec<&str> = issuers.iter().map(|link| link["href"].as_str().unwrap()).collect();
+    assert_eq!(
+        hrefs,
+        ["https://primary.issuers.example", "https://secondary.issuers.example", "https://backup.issuers.example"]
+    );
+    assert_eq!(issuers[0]["properties"]["https://github.com/Mimmikk/RustFinger#priority"], "10");
+    assert!(issuers[2].get("properties").is_none());
+}
+
+#[tokio::test]
+async fn unparseable_files_fail_strict_loads_and_reloads() {
+    let dir = std::env::temp_dir().join(format!("rustfinger-strict-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("good.yml"), "good:\n    domain: \"good.example\"\n    global: true\n").unwrap();
+    std::fs::write(dir.join("bad.yml"), "bad:\n    domain: [unclosed\n").unwrap();
+    
+    // Serving skips the file, --check-config, --lint and reloads must not
+    let lenient = Config::load_dir(&dir, false).await;
+    let strict = Config::load_dir(&dir, true).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(lenient.unwrap().tenants.len(), 1);
+    let err = strict.err().expect("strict load fails").to_string();
+    assert!(err.contains("bad.yml"), "{}", err);
+    
+    let state = AppState::new(Config::from_yaml(CONFIG, URNAliases::new()).unwrap(), Settings::default());
+    crate::reload::apply(&state, Err(err.into()));
+    assert_eq!(state.config.load().tenants.len(), 2);
+}
+
+#[tokio::test]
+async fn wildcard_hrefs_only_take_real_hostnames() {
+    let yaml = r#"
+teams:
+    domain: "teams.example"
+    global: true
+    wildcard_subdomains:
+        "*.teams.example":
+            "http://webfinger.net/rel/profile-page": "https://{subdomain}.teams.example/~{user}"
+"#;
+    let (status, jrd) = webfinger(app_with(yaml), "teams.example", "acct:bob@red.teams.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["links"][0]["href"], "https://red.teams.example/~bob");
+    
+    for payload in ["acct:bob@evil.com%2Fx.teams.example", "acct:bob@evil.com%3Fx.teams.example"] {
+        let (status, _) = webfinger(app_with(yaml), "teams.example", payload).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", payload);
+    }
+}
+
+#[tokio::test]
+async fn stats_need_a_configured_token() {
+    let stats = |settings: Settings, token: Option<&'static str>| async move {
+        let mut request = Request::get("/stats");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        app_with_settings(CONFIG, settings).oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+    };
+    let with_token = || Settings { stats_token: Some("s3cret".to_string()), ..Settings::default() };
+    
+    assert_eq!(stats(Settings::default(), None).await, StatusCode::NOT_FOUND);
+    assert_eq!(stats(with_token(), None).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(stats(with_token(), Some("wrong")).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(stats(with_token(), Some("s3cret")).await, StatusCode::OK);
+}
+
+#[test]
+fn reordering_links_changes_the_config_hash() {
+    let yaml = |first: &str, second: &str| {
+        format!(
+            "order:\n    domain: \"order.example\"\n    users:\n        alice@order.example:\n            \"{}\": \"https://order.example/a\"\n            \"{}\": \"https://order.example/b\"\n",
+            first, second
+        )
+    };
+    let (profile, avatar) = ("http://webfinger.net/rel/profile-page", "http://webfinger.net/rel/avatar");
+    let hash = |yaml: String| Config::from_yaml(&yaml, URNAliases::new()).unwrap().hash;
+    assert_eq!(hash(yaml(profile, avatar)), hash(yaml(profile, avatar)));
+    assert_ne!(hash(yaml(profile, avatar)), hash(yaml(avatar, profile)));
+}
+
+#[tokio::test]
+async fn trailing_dot_hosts_match_the_tenant() {
+    let (status, jrd) = webfinger(app(), "mysite.com.", "acct:alice@mysite.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:alice@mysite.com");
+    
+    let yaml = CONFIG.replace("\"everyone.example\"", "\"everyone.example.\"");
+    let (status, jrd) = webfinger(app_with(&yaml), "everyone.example", "acct:bob@everyone.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["links"][0]["href"], "https://everyone.example/@bob");
+}
+
+#[tokio::test]
+async fn duplicate_tenants_and_domains_name_both_files() {
+    let dir = std::env::temp_dir().join(format!("rustfinger-duplicates-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("first.yml"), "first:\n    domain: \"shared.example\"\n").unwrap();
+    std::fs::write(dir.join("second.yml"), "second:\n    domain: \"shared.example\"\n").unwrap();
+    let domains = Config::load_dir(&dir, true).await;
+    std::fs::write(dir.join("second.yml"), "first:\n    domain: \"other.example\"\n").unwrap();
+    let tenants = Config::load_dir(&dir, true).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+    
+    let err = domains.err().expect("shared domain is refused").to_string();
+    assert!(err.contains("'shared.example'"), "{}", err);
+    assert!(err.contains("first.yml") && err.contains("second.yml"), "{}", err);
+    let err = tenants.err().expect("shared tenant name is refused").to_string();
+    assert!(err.contains("'first'"), "{}", err);
+    assert!(err.contains("first.yml") && err.contains("second.yml"), "{}", err);
+}
+
+#[test]
+fn subjects_are_validated_by_the_email_regex() {
+    use crate::config::normalize_subject;
+    
+    assert_eq!(normalize_subject("alice@mysite.com").unwrap(), "acct:alice@mysite.com");
+    assert_eq!(normalize_subject("acct:ünal@example.xn--p1ai").unwrap(), "acct:ünal@example.xn--p1ai");
+    for subject in ["alice", "alice@", "alice@localhost", "al ice@mysite.com"] {
+        let err = normalize_subject(subject).expect_err("subject is rejected").to_string();
+        assert_eq!(err, format!("Invalid subject format: {}", subject));
+    }
+}
+
+#[tokio::test]
+async fn double_encoded_resources_are_decoded_once() {
+    // axum decodes the query once, leaving "acct%3Aalice%40mysite.com"
+    let (status, jrd) = webfinger(app(), "mysite.com", "acct%253Aalice%2540mysite.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:alice@mysite.com");
+    
+    let (status, jrd) = webfinger(app(), "everyone.example", "acct%253Abob%2540everyone.example").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:bob@everyone.example");
+    assert_eq!(jrd["links"][0]["href"], "https://everyone.example/@bob");
+}
+
+#[tokio::test]
+async fn tenant_urn_aliases_override_the_global_ones() {
+    let yaml = r#"
+blog:
+    domain: "blog.example"
+    urn_aliases:
+        home: "https://blog.example/rel/home"
+    users:
+        bob@blog.example:
+            home: "https://blog.example/bob"
+
+shop:
+    domain: "shop.example"
+    urn_aliases:
+        home: "https://shop.example/rel/home"
+    users:
+        bob@shop.example:
+            home: "https://shop.example/bob"
+
+plain:
+    domain: "plain.example"
+    users:
+        bob@plain.example:
+            home: "https://plain.example/bob"
+"#;
+    let aliases = URNAliases::from([("home".to_string(), "http://webfinger.net/rel/profile-page".to_string())]);
+    let config = Config::from_yaml(yaml, aliases).unwrap();
+    let app = router(AppState::new(config, Settings::default()));
+    
+    for (domain, rel) in [
+        ("blog.example", "https://blog.example/rel/home"),
+        ("shop.example", "https://shop.example/rel/home"),
+        ("plain.example", "http://webfinger.net/rel/profile-page"),
+    ] {
+        let (status, jrd) = webfinger(app.clone(), domain, &format!("acct:bob@{}&rel=home", domain)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(rels(&jrd), vec![rel], "{}", domain);
+    }
+}
+
+#[tokio::test]
+async fn unicode_and_punycode_domains_are_interchangeable() {
+    let unicode = "cafe:\n    domain: \"café.example\"\n    users:\n        bob@café.example:\n            \"http://webfinger.net/rel/profile-page\": \"https://xn--caf-dma.example/bob\"\n";
+    let punycode = unicode.replace("café", "xn--caf-dma");
+    
+    for yaml in [unicode.to_string(), punycode] {
+        for host in ["café.example", "xn--caf-dma.example", "CAFÉ.example"] {
+            for resource in ["acct:bob@café.example", "acct:bob@xn--caf-dma.example"] {
+                let (status, jrd) = webfinger(app_with(&yaml), host, resource).await;
+                assert_eq!(status, StatusCode::OK, "{} {}", host, resource);
+                assert_eq!(jrd["links"][0]["href"], "https://xn--caf-dma.example/bob");
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn composed_and_decomposed_usernames_match() {
+    let yaml = |user: &str| {
+        format!("rene:\n    domain: \"rene.example\"\n    users:\n        {}@rene.example:\n            \"http://schema.org/name\": \"René\"\n", user)
+    };
+    // "é" as one code point (U+00E9), and as "e" with a combining acute accent (U+0301)
+    let (composed, decomposed) = ("ren\u{e9}", "rene\u{301}");
+    let (composed_query, decomposed_query) = ("acct:ren%C3%A9@rene.example", "acct:rene%CC%81@rene.example");
+    
+    for (user, query) in [(composed, decomposed_query), (decomposed, composed_query)] {
+        let (status, jrd) = webfinger(app_with(&yaml(user)), "rene.example", query).await;
+        assert_eq!(status, StatusCode::OK, "{}", query);
+        assert_eq!(jrd["subject"], format!("acct:{}@rene.example", composed));
+    }
+}
+
+#[tokio::test]
+async fn mixed_case_schemes_are_lowercased() {
+    assert_eq!(normalize_resource("ACCT:Bob@Example.com"), "acct:Bob@Example.com");
+    assert_eq!(normalize_resource("MailTo:Bob@Example.com"), "mailto:Bob@Example.com");
+    assert_eq!(extract_domain_from_resource(&normalize_resource("AcCt:bob@example.com")), Some("example.com"));
+    
+    let (status, jrd) = webfinger(app(), "mysite.com", "ACCT:alice@mysite.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["subject"], "acct:alice@mysite.com");
+    
+    let yaml = CONFIG.replace("    domain: \"mysite.com\"\n", "    domain: \"mysite.com\"\n    accept_mailto: true\n");
+    let (status, jrd) = webfinger(app_with(&yaml), "mysite.com", "MailTo:alice@mysite.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jrd["links"][0]["href"], "https://mysite.com/alice");
+}
+
+#[tokio::test]
+async fn readiness_reports_why_the_store_is_not_ready() {
+    use crate::settings::BreakerSettings;
+    use crate::store::CircuitBreaker;
+    
+    let readyz = |app: Router| async move {
+        let response = app.oneshot(Request::get("/readyz").body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    };
+    assert_eq!(readyz(app_with("{}")).await, (StatusCode::SERVICE_UNAVAILABLE, "Not ready: no tenants loaded".to_string()));
+    
+    // Another store answers for itself, through the breaker
+    let flaky = Arc::new(FlakyStore::default());
+    let settings = BreakerSettings { failures: 2, cooldown: Duration::from_millis(50) };
+    let store = Arc::new(CircuitBreaker::new(Arc::new(flaky.clone()), settings));
+    let config = Config::from_yaml("{}", URNAliases::new()).unwrap();
+    let app = router(AppState { store, ..AppState::new(config, Settings::default()) });
+    assert_eq!(readyz(app.clone()).await.0, StatusCode::OK);
+    flaky.failing.store(true, Ordering::SeqCst);
+    assert_eq!(readyz(app).await, (StatusCode::SERVICE_UNAVAILABLE, "Not ready: flaky backend is down".to_string()));
+}
+
+#[tokio::test]
+async fn watch_keeps_dumping_the_last_good_config() {
+    let dir = std::env::temp_dir().join(format!("rustfinger-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("mysite.yml"), CONFIG).unwrap();
+    let good = Config::load_dir(&dir, true).await;
+    std::fs::write(dir.join("mysite.yml"), "mysite:\n    domain: [unclosed\n").unwrap();
+    let broken = Config::load_dir(&dir, true).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+    
+    let mut last_good = None;
+    let mut out = Vec::new();
+    crate::watch::report(&mut out, &mut last_good, good, true).unwrap();
+    let dump = last_good.as_ref().expect("good config is kept").dump().unwrap();
+    out.clear();
+    crate::watch::report(&mut out, &mut last_good, broken, true).unwrap();
+    
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("FAILED: ") && out.contains("mysite.yml"), "{}", out);
+    assert!(out.contains(&format!("Keeping the last good config {}", last_good.unwrap().hash)), "{}", out);
+    assert!(out.contains(&dump), "{}", out);
+}
+
+#[tokio::test]
+async fn batches_are_limited_and_follow_single_lookups() {
+    let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+    let path = std::env::temp_dir().join(format!("rustfinger-batch-{}.pem", std::process::id()));
+    {
+        use ed25519_dalek::pkcs8::{spki::der::pem::LineEnding, EncodePrivateKey};
+        std::fs::write(&path, key.to_pkcs8_pem(LineEnding::LF).unwrap().as_bytes()).unwrap();
+    }
+    let yaml = format!(
+        r#"
+batch:
+    domains: ["batch.example", "old-batch.example"]
+    canonical_domain: "batch.example"
+    signing_key: "{}"
+    users:
+        carol@batch.example:
+            "http://webfinger.net/rel/profile-page": "https://batch.example/carol"
+            "http://schema.org/name": "Carol"
+        oldcarol@batch.example:
+            redirect_to: "acct:carol@batch.example"
+"#,
+        path.display()
+    );
+    let settings = || Settings {
+        batch_max_resources: 3,
+        trust_forwarded_headers: true,
+        rate_limit: Some(crate::settings::RateLimitSettings { per_second: 0.001, burst: 4.0 }),
+        ..Settings::default()
+    };
+    let config = Config::from_yaml(&yaml, URNAliases::new()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let app = router(AppState::new(config, settings()));
+    let batch = |host: &'static str, query: &'static str, client: &'static str| {
+        let request = Request::get(format!("/.well-known/webfinger?batch=1&{}", query))
+            .header(header::HOST, host)
+            .header(header::ACCEPT, "application/xrd+xml")
+            .header("x-forwarded-for", client)
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request)
+    };
+    
+    // Every entry like its single lookup, as JSON and unsigned whatever was asked for
+    let query = "resource=acct:carol@batch.example&resource=acct:oldcarol@batch.example&resource=acct:nobody@batch.example&minimal=1";
+    let response = batch("batch.example", query, "192.0.2.1").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    assert!(!response.headers().contains_key("signature"));
+    let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let entries: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(entries[0]["subject"], "acct:carol@batch.example");
+    assert!(entries[0].get("properties").is_none());
+    assert_eq!(entries[1]["status"], 307);
+    assert_eq!(
+        entries[1]["location"],
+        "https://batch.example/.well-known/webfinger?resource=acct%3Acarol%40batch.example&minimal=1"
+    );
+    assert_eq!(entries[2]["status"], 404);
+    assert_eq!(entries[2]["error"], "resource_not_found");
+    
+    // Each resource took a token, so one more is all that's left
+    let response = batch("batch.example", "resource=acct:carol@batch.example&resource=acct:carol@batch.example", "192.0.2.1");
+    assert_eq!(response.await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+    
+    let query = "resource=acct:a@batch.example&resource=acct:b@batch.example&resource=acct:c@batch.example&resource=acct:d@batch.example";
+    let response = batch("batch.example", query, "192.0.2.2").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap()["error"], "too_many_resources");
+    
+    let response = batch("old-batch.example", "resource=acct:carol@batch.example", "192.0.2.3").await.unwrap();
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    assert!(response.headers()[header::LOCATION].to_str().unwrap().starts_with("https://batch.example/.well-known/webfinger?batch=1&"));
+}
+
+#[tokio::test]
+async fn maintenance_file_is_picked_up_on_reload() {
+    let path = std::env::temp_dir().join(format!("rustfinger-maintenance-{}", std::process::id()));
+    // Refusing an empty config keeps CONFIG serving across the reloads
+    let settings = Settings { maintenance_file: Some(path.clone()), require_tenants: true, ..Settings::default() };
+    let state = AppState::new(Config::from_yaml(CONFIG, URNAliases::new()).unwrap(), settings);
+    let lookup = || webfinger(router(state.clone()), "mysite.com", "acct:alice@mysite.com");
+    assert_eq!(lookup().await.0, StatusCode::OK);
+    
+    std::fs::write(&path, "").unwrap();
+    assert_eq!(lookup().await.0, StatusCode::OK);
+    crate::reload::reload(&state).await;
+    let (status, body) = lookup().await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"], "maintenance");
+    
+    std::fs::remove_file(&path).unwrap();
+    crate::reload::reload(&state).await;
+    assert_eq!(lookup().await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn properties_keep_declaration_order_and_stable_etags() {
+    let yaml = r#"
+ordered:
+    domain: "ordered.example"
+    users:
+        bob@ordered.example:
+            links:
+                "http://webfinger.net/rel/profile-page":
+                    href: "https://ordered.example/bob"
+                    titles: { "zu": "Z", "en": "E", "de": "D" }
+                    properties: { "https://p.example/z": "1", "https://p.example/a": "2" }
+            properties:
+                "https://p.example/zebra": "1"
+                "https://p.example/yak": "2"
+                "https://p.example/xerus": "3"
+                "https://p.example/walrus": "4"
+                "https://p.example/vole": "5"
+                "https://p.example/antelope": "6"
+"#;
+    let fetch = |accept: &'static str| async move {
+        let request = Request::get("/.well-known/webfinger?resource=acct:bob@ordered.example")
+            .header(header::HOST, "ordered.example")
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+        let response = app_with(yaml).oneshot(request).await.unwrap();
+        let etag = response.headers()[header::ETAG].clone();
+        let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (etag, String::from_utf8(body.to_vec()).unwrap())
+    };
+    let in_order = |body: &str, needles: &[&str]| {
+        let positions: Vec<usize> = needles.iter().map(|needle| body.find(needle).expect(needle)).collect();
+        positions.windows(2).all(|pair| pair[0] < pair[1])
+    };
+    let names = ["zebra", "yak", "xerus", "walrus", "vole", "antelope"];
+    
+    for accept in ["application/jrd+json", "application/xrd+xml"] {
+        // Each load builds its maps anew, like another replica would
+        let (etag, body) = fetch(accept).await;
+        assert_eq!((etag, body.clone()), fetch(accept).await);
+        assert!(in_order(&body, &names), "{}", body);
+        assert!(in_order(&body, &["p.example/z", "p.example/a"]), "{}", body);
+        assert!(in_order(&body, &["\"zu\"", "\"en\"", "\"de\""]), "{}", body);
+    }
+}
+
+#[test]
+fn only_guessed_properties_are_url_like_warnings() {
+    let yaml = r#"
+typos:
+    domain: "typos.example"
+    global: true
+    global_links:
+        "http://webfinger.net/rel/avatar": "http//typos.example/@{user}.png"
+    users:
+        bob@typos.example:
+            "http://webfinger.net/rel/profile-page": "https//typos.example/bob"
+            "http://schema.org/name": "Bob"
+            properties:
+                "http://schema.org/url": "https://typos.example/bob"
+"#;
+    let config = Config::from_yaml(yaml, URNAliases::new()).unwrap();
+    assert_eq!(
+        config.url_like_properties,
+        vec![
+            "Tenant 'typos', user '*': property 'http://webfinger.net/rel/avatar' looks like a URL but isn't one: http//typos.example/@{user}.png",
+            "Tenant 'typos', user 'acct:bob@typos.example': property 'http://webfinger.net/rel/profile-page' looks like a URL but isn't one: https//typos.example/bob",
+        ]
+    );
+}
+
+// Fails every write like stdout does once `| head` has exited
+struct ClosedPipe;
+
+impl std::io::Write for ClosedPipe {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::ErrorKind::BrokenPipe.into())
+    }
+    
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn dumps_stop_quietly_on_a_closed_pipe() {
+    let dump = Config::from_yaml(CONFIG, URNAliases::new()).unwrap().dump().unwrap();
+    assert!(crate::server::print_to(&mut ClosedPipe, &dump).is_ok());
+    
+    let mut out = Vec::new();
+    crate::server::print_to(&mut out, &dump).unwrap();
+    crate::server::print_to(&mut out, "yaml: ends in a newline\n").unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), format!("{}\nyaml: ends in a newline\n", dump));
+}
@@ -0,0 +1,25 @@
+use axum_server::tls_rustls::RustlsConfig;
+use std::env;
+use tracing::info;
+
+// Load the TLS certificate and key when both TLS_CERT and TLS_KEY are set
+pub async fn load_tls_config() -> Result<Option<RustlsConfig>, Box<dyn std::error::Error>> {
+    let cert = env::var("TLS_CERT").ok().filter(|v| !v.is_empty());
+    let key = env::var("TLS_KEY").ok().filter(|v| !v.is_empty());
+    
+    let (cert, key) = match (cert, key) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) => return Ok(None), // Plain HTTP
+        _ => return Err("TLS_CERT and TLS_KEY must be set together".into()),
+    };
+    
+    // Use ring as the process-wide crypto provider
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    
+    let config = RustlsConfig::from_pem_file(&cert, &key)
+        .await
+        .map_err(|e| format!("Failed to load TLS certificate '{}' / key '{}': {}", cert, key, e))?;
+    
+    info!("TLS enabled with certificate '{}'", cert);
+    Ok(Some(config))
+}
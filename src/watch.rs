@@ -0,0 +1,82 @@
+use std::io::Write;
+use std::path::Path;
+use tokio::sync::mpsc;
+
+use crate::config::{self, Config};
+use crate::reload;
+
+// URN aliases shared by every tenant, next to the config directory
+const URN_FILE: &str = "urns.yml";
+
+// --watch: validate on every change to the tenants or urns.yml and print what
+// loaded, without serving. Files that fail to parse count as errors here, and
+// a broken edit keeps the last good config in memory, so with --dump-config
+// its dump is printed again after the error
+pub async fn run(dump: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (dir, file_name) = reload::config_target();
+    let _tenant_watcher = reload::watch(&dir, file_name, tx.clone())
+        .map_err(|e| format!("Failed to watch '{}': {}", dir.display(), e))?;
+    let _urn_watcher = reload::watch(Path::new("."), Some(URN_FILE.into()), tx)
+        .map_err(|e| format!("Failed to watch '{}': {}", URN_FILE, e))?;
+    println!("Watching '{}' and '{}', Ctrl+C to stop", config::tenant_source(), URN_FILE);
+    
+    let mut last_good = None;
+    loop {
+        let loaded = Config::load_strict().await;
+        report(&mut std::io::stdout().lock(), &mut last_good, loaded, dump)?;
+        if reload::next_change(&mut rx).await.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+// Print the result of one check, keeping the config in `last_good` when it loaded
+pub(crate) fn report(
+    out: &mut impl Write,
+    last_good: &mut Option<Config>,
+    loaded: Result<Config, Box<dyn std::error::Error>>,
+    dump: bool,
+) -> std::io::Result<()> {
+    writeln!(out)?;
+    match loaded {
+        Ok(config) => {
+            writeln!(out, "OK: {} tenant(s) loaded, config {}", config.tenants.len(), config.hash)?;
+            summarize(out, &config)?;
+            if dump {
+                print_dump(out, &config)?;
+            }
+            *last_good = Some(config);
+        }
+        Err(e) => {
+            writeln!(out, "FAILED: {}", e)?;
+            match last_good {
+                Some(config) => {
+                    writeln!(out, "Keeping the last good config {}", config.hash)?;
+                    if dump {
+                        print_dump(out, config)?;
+                    }
+                }
+                None => writeln!(out, "No good config loaded yet")?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_dump(out: &mut impl Write, config: &Config) -> std::io::Result<()> {
+    match config.dump() {
+        Ok(dump) => writeln!(out, "{}", dump),
+        Err(e) => writeln!(out, "Failed to dump config: {}", e),
+    }
+}
+
+// One line per tenant, by name
+fn summarize(out: &mut impl Write, config: &Config) -> std::io::Result<()> {
+    let mut tenants: Vec<_> = config.tenants.values().collect();
+    tenants.sort_by(|a, b| a.name.cmp(&b.name));
+    for tenant in tenants {
+        writeln!(out, "  {}: {} ({} finger(s))", tenant.name, tenant.domains.join(", "), tenant.fingers.len())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use axum::http::{header, HeaderMap};
+use indexmap::IndexMap;
+
+use crate::config::{Link, WebFinger};
+use crate::jrd::JRD_CONTENT_TYPE;
+
+pub const XRD_CONTENT_TYPE: &str = "application/xrd+xml";
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+// Representation of a discovery document, JRD unless the client prefers XRD
+// or plain JSON (the same body, for tools that only accept application/json)
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Format {
+    Jrd,
+    Json,
+    Xrd,
+}
+
+impl Format {
+    pub fn negotiate(headers: &HeaderMap) -> Self {
+        let mut xrd = None;
+        let mut jrd = None;
+        let mut json = None;
+        let mut wildcard = 0.0f32;
+        
+        for range in headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+        {
+            let mut params = range.split(';');
+            let media_type = params.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let quality = params
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            
+            let slot = match media_type.as_str() {
+                XRD_CONTENT_TYPE => &mut xrd,
+                JRD_CONTENT_TYPE => &mut jrd,
+                JSON_CONTENT_TYPE => &mut json,
+                "*/*" | "application/*" => {
+                    wildcard = wildcard.max(quality);
+                    continue;
+                }
+                _ => continue,
+            };
+            *slot = Some(slot.unwrap_or(0.0f32).max(quality));
+        }
+        
+        // Ties go to JRD, the WebFinger default (RFC 7033 section 10.2)
+        let (xrd, jrd, json) = (xrd.unwrap_or(wildcard), jrd.unwrap_or(wildcard), json.unwrap_or(wildcard));
+        if xrd > jrd.max(json) {
+            Format::Xrd
+        } else if json > jrd {
+            Format::Json
+        } else {
+            Format::Jrd
+        }
+    }
+    
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Jrd => JRD_CONTENT_TYPE,
+            Format::Json => JSON_CONTENT_TYPE,
+            Format::Xrd => XRD_CONTENT_TYPE,
+        }
+    }
+}
+
+pub fn finger_to_xrd(finger: &WebFinger) -> String {
+    render(Some(&finger.subject), &finger.aliases, &finger.properties, &finger.links)
+}
+
+// XRD 1.0 document, the XML counterpart of a JRD (RFC 6415 appendix A)
+pub fn render(
+    subject: Option<&str>,
+    aliases: &[String],
+    properties: &IndexMap<String, Option<String>>,
+    links: &[Link],
+) -> String {
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<XRD xmlns=\"http://docs.oasis-open.org/ns/xri/xrd-1.0\" ",
+        "xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n",
+    ));
+    
+    if let Some(subject) = subject {
+        xml.push_str(&format!("  <Subject>{}</Subject>\n", escape_xml(subject)));
+    }
+    for alias in aliases {
+        xml.push_str(&format!("  <Alias>{}</Alias>\n", escape_xml(alias)));
+    }
+    for (kind, value) in properties {
+        push_property(&mut xml, "  ", kind, value.as_deref());
+    }
+    
+    for link in links {
+        xml.push_str(&format!("  <Link rel=\"{}\"", escape_xml(&link.rel)));
+        if let Some(href) = &link.href {
+            xml.push_str(&format!(" href=\"{}\"", escape_xml(href)));
+        }
+        if let Some(template) = &link.template {
+            xml.push_str(&format!(" template=\"{}\"", escape_xml(template)));
+        }
+        
+        if link.titles.is_empty() && link.properties.is_empty() {
+            xml.push_str("/>\n");
+            continue;
+        }
+        
+        xml.push_str(">\n");
+        for (lang, title) in &link.titles {
+            // "und" is JRD's way of saying the language is unknown
+            if lang == "und" {
+                xml.push_str(&format!("    <Title>{}</Title>\n", escape_xml(title)));
+            } else {
+                xml.push_str(&format!(
+                    "    <Title xml:lang=\"{}\">{}</Title>\n",
+                    escape_xml(lang),
+                    escape_xml(title)
+                ));
+            }
+        }
+        for (kind, value) in &link.properties {
+            push_property(&mut xml, "    ", kind, value.as_deref());
+        }
+        xml.push_str("  </Link>\n");
+    }
+    
+    xml.push_str("</XRD>\n");
+    xml
+}
+
+fn push_property(xml: &mut String, indent: &str, kind: &str, value: Option<&str>) {
+    match value {
+        Some(value) => xml.push_str(&format!(
+            "{}<Property type=\"{}\">{}</Property>\n",
+            indent,
+            escape_xml(kind),
+            escape_xml(value)
+        )),
+        None => xml.push_str(&format!(
+            "{}<Property type=\"{}\" xsi:nil=\"true\"/>\n",
+            indent,
+            escape_xml(kind)
+        )),
+    }
+}
+
+pub fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}